@@ -6,6 +6,7 @@ use std::time::SystemTime;
 pub struct Authed {
     pub user: String,
     pub from: SystemTime,
+    pub expires: Option<SystemTime>,
 }
 
 pub type Users = Vec<User>;
@@ -18,6 +19,15 @@ pub struct User {
     pub lang: String,
     pub master: bool,
     pub access: Vec<Access>,
+    pub quota: Option<Quota>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Quota {
+    pub bytes: Option<u64>,
+    pub files: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,6 +38,7 @@ pub enum Access {
     DIR {
         path: String,
         can_write: bool,
+        versions: Option<u64>,
     },
     CMD {
         name: String,
@@ -0,0 +1,220 @@
+use actix_web::error::{Error, ErrorBadRequest, ErrorForbidden, ErrorNotFound};
+use actix_web::HttpResponse;
+use liz::{liz_dbg_errs, liz_paths};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::auth::User;
+use crate::body::Body;
+use crate::conflicts::Conflict;
+use crate::dirs;
+use crate::files;
+use crate::guard;
+use crate::utils::generate_id;
+use crate::versions;
+use crate::SrvResult;
+
+static FINISHED_TTL: Duration = Duration::from_secs(60 * 60);
+
+static JOBS: Lazy<Mutex<HashMap<String, BatchJob>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOp {
+    New,
+    Copy,
+    Move,
+    Del,
+    Write,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchItem {
+    pub op: BatchOp,
+    pub path: Option<String>,
+    pub origin: Option<String>,
+    pub destiny: Option<String>,
+    pub conflict: Option<Conflict>,
+    #[serde(default)]
+    pub base64: bool,
+    pub data: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Batch {
+    pub items: Vec<BatchItem>,
+    #[serde(default)]
+    pub continue_on_error: bool,
+    #[serde(default)]
+    pub background: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemResult {
+    pub index: usize,
+    pub success: bool,
+    pub status: u16,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchJob {
+    pub id: String,
+    #[serde(skip)]
+    pub owner: String,
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub finished: bool,
+    pub results: Vec<ItemResult>,
+    #[serde(skip)]
+    pub finished_at: Option<Instant>,
+}
+
+pub fn run(batch: Batch, for_user: &User, srv_data: Arc<Body>) -> SrvResult {
+    if batch.items.is_empty() {
+        return Err(ErrorBadRequest("The batch has no items."));
+    }
+    if !batch.background {
+        let mut results = Vec::new();
+        execute(&batch, for_user, &srv_data, |result| results.push(result));
+        return Ok(HttpResponse::Ok().json(results));
+    }
+    let id = generate_id(24);
+    let job = BatchJob {
+        id: id.clone(),
+        owner: for_user.name.clone(),
+        total: batch.items.len(),
+        done: 0,
+        failed: 0,
+        finished: false,
+        results: Vec::new(),
+        finished_at: None,
+    };
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.retain(|_, job| {
+            job.finished_at
+                .map(|finished_at| finished_at.elapsed() < FINISHED_TTL)
+                .unwrap_or(true)
+        });
+        jobs.insert(id.clone(), job.clone());
+    }
+    let user = for_user.clone();
+    std::thread::spawn(move || {
+        execute(&batch, &user, &srv_data, |result| {
+            if let Some(job) = JOBS.lock().unwrap().get_mut(&id) {
+                job.done += 1;
+                if !result.success {
+                    job.failed += 1;
+                }
+                job.results.push(result);
+            }
+        });
+        if let Some(job) = JOBS.lock().unwrap().get_mut(&id) {
+            job.finished = true;
+            job.finished_at = Some(Instant::now());
+        }
+    });
+    Ok(HttpResponse::Ok().json(job))
+}
+
+pub fn status(id: &str, for_user: &User) -> SrvResult {
+    let jobs = JOBS.lock().unwrap();
+    let job = jobs
+        .get(id)
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the batch", id)))?;
+    if job.owner != for_user.name && !for_user.master {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    Ok(HttpResponse::Ok().json(job))
+}
+
+fn execute(batch: &Batch, for_user: &User, srv_data: &Body, mut on_result: impl FnMut(ItemResult)) {
+    for (index, item) in batch.items.iter().enumerate() {
+        let result = match execute_item(item, for_user, srv_data) {
+            Ok(response) => ItemResult {
+                index,
+                success: response.status().is_success(),
+                status: response.status().as_u16(),
+                error: None,
+            },
+            Err(err) => ItemResult {
+                index,
+                success: false,
+                status: err.as_response_error().status_code().as_u16(),
+                error: Some(format!("{}", err)),
+            },
+        };
+        let success = result.success;
+        on_result(result);
+        if !success && !batch.continue_on_error {
+            break;
+        }
+    }
+}
+
+fn execute_item(item: &BatchItem, for_user: &User, srv_data: &Body) -> SrvResult {
+    let to_trash = srv_data.head.dirs_trash;
+    match item.op {
+        BatchOp::New => {
+            let path = get_path(&item.path, for_user)?;
+            guard::check_dir_access(&path, None, "/dir/new", for_user)?;
+            dirs::new(&path)
+        }
+        BatchOp::Write => {
+            let path = get_path(&item.path, for_user)?;
+            guard::check_dir_access(&path, None, "/file/write", for_user)?;
+            let data = item
+                .data
+                .as_ref()
+                .ok_or_else(|| ErrorBadRequest("The data to write was not informed."))?;
+            let keep_versions = versions::get_limit(&path, for_user, &srv_data.head);
+            files::write(&path, item.base64, data, keep_versions, for_user)
+        }
+        BatchOp::Del => {
+            let path = get_path(&item.path, for_user)?;
+            if Path::new(&path).is_dir() {
+                guard::check_dir_access(&path, None, "/dir/del", for_user)?;
+                dirs::del(&path, to_trash, for_user)
+            } else {
+                guard::check_dir_access(&path, None, "/file/del", for_user)?;
+                files::del(&path, to_trash, for_user)
+            }
+        }
+        BatchOp::Copy | BatchOp::Move => {
+            let origin = get_path(&item.origin, for_user)?;
+            let destiny = get_path(&item.destiny, for_user)?;
+            let is_dir = Path::new(&origin).is_dir();
+            let moving = matches!(item.op, BatchOp::Move);
+            let resource = match (is_dir, moving) {
+                (true, true) => "/dir/move",
+                (true, false) => "/dir/copy",
+                (false, true) => "/file/move",
+                (false, false) => "/file/copy",
+            };
+            guard::check_dir_access(&origin, Some(&destiny), resource, for_user)?;
+            match (is_dir, moving) {
+                (true, true) => dirs::mov(&origin, &destiny, item.conflict, false, for_user),
+                (true, false) => dirs::copy(&origin, &destiny, item.conflict, false, for_user),
+                (false, true) => files::mov(&origin, &destiny, item.conflict, false, for_user),
+                (false, false) => files::copy(&origin, &destiny, item.conflict, false, for_user),
+            }
+        }
+    }
+}
+
+fn get_path(path: &Option<String>, for_user: &User) -> Result<String, Error> {
+    let path = path
+        .as_ref()
+        .ok_or_else(|| ErrorBadRequest("The path of the batch item was not informed."))?;
+    liz_paths::path_join_if_relative(&for_user.home, path)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, &for_user.home, path)))
+}
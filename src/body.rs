@@ -5,13 +5,15 @@ use serde_json;
 use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
 use std::time::SystemTime;
 
 use crate::auth::{Authed, User, Users};
 use crate::base::{Base, Bases};
 use crate::conf::Head;
 use crate::pooling::Pool;
+use crate::schedules::{self, Schedules};
+use crate::shares::{self, Shares};
 
 #[derive(Debug)]
 pub struct Body {
@@ -21,26 +23,30 @@ pub struct Body {
     pub pooling: Pool,
     pub srv_dir: String,
     pub server: RwLock<Option<Server>>,
-    pub tokens: RwLock<HashMap<String, Authed>>,
+    pub tokens: Arc<RwLock<HashMap<String, Authed>>>,
+    pub shares: RwLock<Shares>,
+    pub schedules: RwLock<Schedules>,
     pub last_clean: SystemTime,
 }
 
 impl Body {
-    pub fn new(head: Head) -> Self {
+    pub fn new(head: Head) -> std::io::Result<Self> {
         let srv_dir = Body::init_working_dir();
         let users = Body::init_users(&srv_dir);
         let bases = Body::init_bases(&users);
         let pooling = Pool::new();
-        Body {
+        Ok(Body {
             head,
             users,
             bases,
             pooling,
             srv_dir,
             server: RwLock::new(None),
-            tokens: RwLock::new(HashMap::new()),
+            tokens: Arc::new(RwLock::new(HashMap::new())),
+            shares: RwLock::new(shares::load()?),
+            schedules: RwLock::new(schedules::load()?),
             last_clean: SystemTime::now(),
-        }
+        })
     }
 
     fn init_working_dir() -> String {
@@ -66,6 +72,8 @@ impl Body {
                 lang: String::new(),
                 master: true,
                 access: Vec::new(),
+                quota: None,
+                groups: Vec::new(),
             };
             users.push(user);
         }
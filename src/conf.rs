@@ -3,11 +3,29 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::auth::Quota;
+use crate::limits::CmdLimits;
+use crate::links::SymlinkPolicy;
 use crate::QinServer;
 
 static DEFAULT_NAME: &str = "QinpelSrv";
 static DEFAULT_HOST: &str = "localhost";
 static DEFAULT_PORT: u64 = 5490;
+static DEFAULT_TRASH_RETENTION: u64 = 30;
+static DEFAULT_FILE_VERSIONS: u64 = 0;
+static DEFAULT_WEBDAV_PUT_CAP: u64 = 1024 * 1024 * 1024;
+static DEFAULT_CMD_OUTPUT_CAP: usize = 1024 * 1024;
+static DEFAULT_CMD_TOKEN_TTL: u64 = 15 * 60;
+static DEFAULT_CMD_ENV_ALLOW: &[&str] = &[
+    "PATH",
+    "LANG",
+    "LC_ALL",
+    "TZ",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SYSTEMROOT",
+];
 
 #[derive(Debug)]
 pub struct Head {
@@ -24,6 +42,17 @@ pub struct Head {
     pub serves_sqls: bool,
     pub serves_lizs: bool,
     pub redirects: Option<HashMap<String, String>>,
+    pub dirs_trash: bool,
+    pub trash_retention: u64,
+    pub file_versions: u64,
+    pub group_quotas: HashMap<String, Quota>,
+    pub webdav_prefix: Option<String>,
+    pub webdav_put_cap: u64,
+    pub symlink_policy: SymlinkPolicy,
+    pub cmd_output_cap: usize,
+    pub cmd_limits: HashMap<String, CmdLimits>,
+    pub cmd_env_allow: Vec<String>,
+    pub cmd_token_ttl: u64,
 }
 
 impl Head {
@@ -41,6 +70,20 @@ impl Head {
         let mut setup_sqls = false;
         let mut setup_lizs = false;
         let mut setup_redirects: Option<HashMap<String, String>> = None;
+        let mut setup_dirs_trash = false;
+        let mut setup_trash_retention = DEFAULT_TRASH_RETENTION;
+        let mut setup_file_versions = DEFAULT_FILE_VERSIONS;
+        let mut setup_webdav_prefix: Option<String> = None;
+        let mut setup_webdav_put_cap = DEFAULT_WEBDAV_PUT_CAP;
+        let mut setup_symlink_policy = SymlinkPolicy::default();
+        let mut setup_cmd_output_cap = DEFAULT_CMD_OUTPUT_CAP;
+        let mut setup_group_quotas: HashMap<String, Quota> = HashMap::new();
+        let mut setup_cmd_limits: HashMap<String, CmdLimits> = HashMap::new();
+        let mut setup_cmd_env_allow: Vec<String> = DEFAULT_CMD_ENV_ALLOW
+            .iter()
+            .map(|name| String::from(*name))
+            .collect();
+        let mut setup_cmd_token_ttl = DEFAULT_CMD_TOKEN_TTL;
         let path = Path::new("setup.json");
         if path.exists() {
             let file = std::fs::File::open(path).expect("Setup file exists but could not be open.");
@@ -141,6 +184,97 @@ impl Head {
                 }
                 _ => {}
             };
+            match &setup_file["dirsTrash"] {
+                Value::Bool(dirs_trash) => {
+                    setup_dirs_trash = *dirs_trash;
+                }
+                _ => {}
+            };
+            match &setup_file["trashRetention"] {
+                Value::Number(trash_retention) => {
+                    setup_trash_retention = trash_retention
+                        .as_u64()
+                        .expect("Could not parse the trash retention from setup file.");
+                }
+                _ => {}
+            };
+            match &setup_file["fileVersions"] {
+                Value::Number(file_versions) => {
+                    setup_file_versions = file_versions
+                        .as_u64()
+                        .expect("Could not parse the file versions from setup file.");
+                }
+                _ => {}
+            };
+            match &setup_file["webdavPrefix"] {
+                Value::String(webdav_prefix) => {
+                    let webdav_prefix = webdav_prefix.trim_end_matches('/');
+                    if !webdav_prefix.is_empty() {
+                        setup_webdav_prefix = Some(String::from(webdav_prefix));
+                    }
+                }
+                _ => {}
+            };
+            match &setup_file["webdavPutCap"] {
+                Value::Number(webdav_put_cap) => {
+                    setup_webdav_put_cap = webdav_put_cap
+                        .as_u64()
+                        .expect("Could not parse the WebDAV put cap from setup file.");
+                }
+                _ => {}
+            };
+            match &setup_file["symlinkPolicy"] {
+                Value::String(symlink_policy) => {
+                    setup_symlink_policy = SymlinkPolicy::parse(symlink_policy)
+                        .expect("Could not parse the symlink policy from setup file.");
+                }
+                _ => {}
+            };
+            match &setup_file["cmdOutputCap"] {
+                Value::Number(cmd_output_cap) => {
+                    setup_cmd_output_cap = cmd_output_cap
+                        .as_u64()
+                        .expect("Could not parse the command output cap from setup file.")
+                        as usize;
+                }
+                _ => {}
+            };
+            match &setup_file["groupQuotas"] {
+                Value::Object(_) => {
+                    setup_group_quotas = serde_json::from_value(setup_file["groupQuotas"].clone())
+                        .expect("Could not parse the group quotas from setup file.");
+                }
+                _ => {}
+            };
+            match &setup_file["cmdLimits"] {
+                Value::Object(_) => {
+                    setup_cmd_limits = serde_json::from_value(setup_file["cmdLimits"].clone())
+                        .expect("Could not parse the command limits from setup file.");
+                }
+                _ => {}
+            };
+            match &setup_file["cmdEnvAllow"] {
+                Value::Array(cmd_env_allow) => {
+                    setup_cmd_env_allow = cmd_env_allow
+                        .iter()
+                        .map(|name| {
+                            String::from(
+                                name.as_str()
+                                    .expect("Could not parse the command env allow from setup file."),
+                            )
+                        })
+                        .collect();
+                }
+                _ => {}
+            };
+            match &setup_file["cmdTokenTtl"] {
+                Value::Number(cmd_token_ttl) => {
+                    setup_cmd_token_ttl = cmd_token_ttl
+                        .as_u64()
+                        .expect("Could not parse the command token ttl from setup file.");
+                }
+                _ => {}
+            };
         }
         if let Some(verbose) = qinpel_srv.verbose {
             setup_verbose = verbose;
@@ -207,6 +341,17 @@ impl Head {
             serves_sqls: setup_sqls,
             serves_lizs: setup_lizs,
             redirects: setup_redirects,
+            dirs_trash: setup_dirs_trash,
+            trash_retention: setup_trash_retention,
+            file_versions: setup_file_versions,
+            group_quotas: setup_group_quotas,
+            webdav_prefix: setup_webdav_prefix,
+            webdav_put_cap: setup_webdav_put_cap,
+            symlink_policy: setup_symlink_policy,
+            cmd_output_cap: setup_cmd_output_cap,
+            cmd_limits: setup_cmd_limits,
+            cmd_env_allow: setup_cmd_env_allow,
+            cmd_token_ttl: setup_cmd_token_ttl,
         }
     }
 
@@ -0,0 +1,249 @@
+use actix_web::error::{Error, ErrorBadRequest, ErrorConflict};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use serde::{Deserialize, Serialize};
+
+use std::path::{Path, PathBuf};
+
+use crate::dirs::{self, Report};
+use crate::links;
+use crate::SrvResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflict {
+    Fail,
+    Overwrite,
+    Skip,
+    Rename,
+    Merge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Copy,
+    Move,
+    Replace,
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Operation {
+    pub action: Action,
+    pub origin: String,
+    pub destiny: String,
+}
+
+impl Operation {
+    fn new(action: Action, origin: &Path, destiny: &Path) -> Self {
+        Operation {
+            action,
+            origin: format!("{}", origin.display()),
+            destiny: format!("{}", destiny.display()),
+        }
+    }
+}
+
+pub fn run(
+    origin: &str,
+    destiny: &str,
+    conflict: Conflict,
+    dry_run: bool,
+    moving: bool,
+) -> SrvResult {
+    let origin_pathed = Path::new(origin);
+    let operations = plan(origin_pathed, Path::new(destiny), conflict, moving)?;
+    if dry_run {
+        return Ok(HttpResponse::Ok().json(operations));
+    }
+    let mut report = Report::new(origin, destiny);
+    execute(&operations, moving, &mut report);
+    if moving && conflict == Conflict::Merge && report.failed.is_empty() {
+        if origin_pathed.exists() {
+            std::fs::remove_dir_all(origin_pathed)?;
+        }
+    }
+    Ok(HttpResponse::Ok().json(report))
+}
+
+pub fn plan(
+    origin: &Path,
+    destiny: &Path,
+    conflict: Conflict,
+    moving: bool,
+) -> Result<Vec<Operation>, Error> {
+    let action = if moving { Action::Move } else { Action::Copy };
+    if !destiny.exists() {
+        return Ok(vec![Operation::new(action, origin, destiny)]);
+    }
+    match conflict {
+        Conflict::Fail => Err(ErrorConflict(liz_dbg_errs!(
+            "The destiny already exists",
+            destiny
+        ))),
+        Conflict::Overwrite => Ok(vec![Operation::new(Action::Replace, origin, destiny)]),
+        Conflict::Skip => Ok(vec![Operation::new(Action::Skip, origin, destiny)]),
+        Conflict::Rename => Ok(vec![Operation::new(
+            action,
+            origin,
+            &get_free_name(destiny),
+        )]),
+        Conflict::Merge => {
+            if !origin.is_dir() || !destiny.is_dir() {
+                return Err(ErrorBadRequest(liz_dbg_errs!(
+                    "Only directories can be merged",
+                    origin,
+                    destiny
+                )));
+            }
+            let mut operations = Vec::new();
+            plan_merge(origin, destiny, action, &mut operations)?;
+            Ok(operations)
+        }
+    }
+}
+
+fn plan_merge(
+    origin: &Path,
+    destiny: &Path,
+    action: Action,
+    operations: &mut Vec<Operation>,
+) -> Result<(), Error> {
+    for entry in origin.read_dir()? {
+        let entry = entry?;
+        let inside = entry.path();
+        let target = destiny.join(entry.file_name());
+        if !target.exists() && !links::is_link(&target) {
+            operations.push(Operation::new(action, &inside, &target));
+        } else if links::is_real_dir(&inside) && links::is_real_dir(&target) {
+            plan_merge(&inside, &target, action, operations)?;
+        } else {
+            operations.push(Operation::new(Action::Replace, &inside, &target));
+        }
+    }
+    Ok(())
+}
+
+pub fn execute(operations: &[Operation], moving: bool, report: &mut Report) {
+    for operation in operations {
+        let origin = Path::new(&operation.origin);
+        let destiny = Path::new(&operation.destiny);
+        if operation.action == Action::Skip {
+            report.skipped += 1;
+            continue;
+        }
+        if operation.action == Action::Replace {
+            let removed = if links::is_real_dir(destiny) {
+                std::fs::remove_dir_all(destiny)
+            } else {
+                std::fs::remove_file(destiny)
+            };
+            if let Err(err) = removed {
+                report.fail(destiny, err);
+                continue;
+            }
+        }
+        if moving {
+            if let Err(err) = dirs::move_all(origin, destiny, report) {
+                report.fail(origin, err);
+            }
+        } else if links::is_real_dir(origin) {
+            dirs::copy_dir_all(origin, destiny, report);
+        } else if let Err(err) = dirs::copy_file_all(origin, destiny) {
+            report.fail(origin, err);
+        } else {
+            report.copied += 1;
+        }
+    }
+}
+
+fn get_free_name(destiny: &Path) -> PathBuf {
+    let parent = destiny.parent().unwrap_or_else(|| Path::new(""));
+    let stem = destiny
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = destiny
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let mut attempt = 1;
+    loop {
+        let candidate = parent.join(format!("{} ({}){}", stem, attempt, extension));
+        if !candidate.exists() {
+            return candidate;
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_dirs() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("origin/inner")).unwrap();
+        std::fs::write(root.path().join("origin/one.txt"), "one").unwrap();
+        std::fs::write(root.path().join("origin/inner/two.txt"), "two").unwrap();
+        std::fs::create_dir_all(root.path().join("destiny/inner")).unwrap();
+        std::fs::write(root.path().join("destiny/one.txt"), "old").unwrap();
+        root
+    }
+
+    #[test]
+    fn plan_copies_when_destiny_is_free() {
+        let root = make_dirs();
+        let origin = root.path().join("origin");
+        let destiny = root.path().join("free");
+        let operations = plan(&origin, &destiny, Conflict::Fail, false).unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].action, Action::Copy);
+    }
+
+    #[test]
+    fn plan_fails_skips_or_replaces_existing() {
+        let root = make_dirs();
+        let origin = root.path().join("origin/one.txt");
+        let destiny = root.path().join("destiny/one.txt");
+        assert!(plan(&origin, &destiny, Conflict::Fail, false).is_err());
+        let skipped = plan(&origin, &destiny, Conflict::Skip, false).unwrap();
+        assert_eq!(skipped[0].action, Action::Skip);
+        let replaced = plan(&origin, &destiny, Conflict::Overwrite, true).unwrap();
+        assert_eq!(replaced[0].action, Action::Replace);
+    }
+
+    #[test]
+    fn plan_renames_to_a_free_name() {
+        let root = make_dirs();
+        let origin = root.path().join("origin/one.txt");
+        let destiny = root.path().join("destiny/one.txt");
+        std::fs::write(root.path().join("destiny/one (1).txt"), "taken").unwrap();
+        let renamed = plan(&origin, &destiny, Conflict::Rename, false).unwrap();
+        assert_eq!(renamed[0].action, Action::Copy);
+        assert!(renamed[0].destiny.ends_with("one (2).txt"));
+    }
+
+    #[test]
+    fn plan_merges_directories_entry_by_entry() {
+        let root = make_dirs();
+        let origin = root.path().join("origin");
+        let destiny = root.path().join("destiny");
+        let mut operations = plan(&origin, &destiny, Conflict::Merge, false).unwrap();
+        operations.sort_by(|a, b| a.origin.cmp(&b.origin));
+        assert_eq!(operations.len(), 2);
+        assert!(operations[0].origin.ends_with("two.txt"));
+        assert_eq!(operations[0].action, Action::Copy);
+        assert!(operations[1].origin.ends_with("one.txt"));
+        assert_eq!(operations[1].action, Action::Replace);
+    }
+
+    #[test]
+    fn plan_refuses_to_merge_files() {
+        let root = make_dirs();
+        let origin = root.path().join("origin/one.txt");
+        let destiny = root.path().join("destiny/one.txt");
+        assert!(plan(&origin, &destiny, Conflict::Merge, false).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use actix_web::error::{Error, ErrorBadRequest, InternalError};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use diffy::{DiffOptions, Patch};
+use liz::liz_dbg_errs;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::auth::User;
+use crate::files;
+use crate::quotas;
+use crate::srvdirs::PathDiff;
+use crate::versions;
+use crate::SrvResult;
+
+static DEFAULT_CONTEXT: usize = 3;
+
+pub fn diff(path: &str, other: Option<&str>, params: &PathDiff) -> SrvResult {
+    let original = read_text(path)?;
+    let (modified, modified_name) = match other {
+        Some(other) => (read_text(other)?, String::from(other)),
+        None => {
+            let data = params.data.as_ref().ok_or_else(|| {
+                ErrorBadRequest("The other path or the data to compare must be informed.")
+            })?;
+            let bytes = files::get_data(params.base64, data)?;
+            let text = String::from_utf8(bytes)
+                .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, path)))?;
+            (text, String::from(path))
+        }
+    };
+    let patch = DiffOptions::new()
+        .set_context_len(params.context.unwrap_or(DEFAULT_CONTEXT))
+        .create_patch(&original, &modified);
+    if patch.hunks().is_empty() {
+        return Ok(HttpResponse::Ok().content_type("text/x-diff").body(""));
+    }
+    let body = patch.to_string().replacen(
+        "--- original\n+++ modified\n",
+        &format!("--- {}\n+++ {}\n", path, modified_name),
+        1,
+    );
+    Ok(HttpResponse::Ok().content_type("text/x-diff").body(body))
+}
+
+pub fn patch(path: &str, diff: &str, keep_versions: u64, for_user: &User) -> SrvResult {
+    let original = read_text(path)?;
+    let parsed = Patch::from_str(diff)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!("The patch is not valid", err)))?;
+    let patched = diffy::apply(&original, &parsed).map_err(|err| -> Error {
+        InternalError::new(
+            liz_dbg_errs!("The patch does not apply", err, path),
+            StatusCode::CONFLICT,
+        )
+        .into()
+    })?;
+    let grows = (patched.len() as u64).saturating_sub(original.len() as u64);
+    let reserved = quotas::reserve(path, for_user, grows, 0)?;
+    versions::save(path, keep_versions)?;
+    replace_atomic(Path::new(path), patched.as_bytes())?;
+    reserved.keep();
+    Ok(HttpResponse::Ok().body(format!("Patched on: {}", path)))
+}
+
+fn read_text(path: &str) -> Result<String, Error> {
+    let pathed = Path::new(path);
+    if !pathed.is_file() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path is not a file",
+            path
+        )));
+    }
+    let bytes = std::fs::read(pathed)?;
+    String::from_utf8(bytes)
+        .map_err(|_| ErrorBadRequest(liz_dbg_errs!("The file is not an UTF-8 text", path)))
+}
+
+fn replace_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let suffix: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    let temp = parent.join(format!(".{}.patch-{}", name, suffix));
+    let result = (|| {
+        let mut file = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        std::fs::set_permissions(&temp, std::fs::metadata(path)?.permissions())?;
+        std::fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
@@ -1,11 +1,55 @@
 use actix_web::error::ErrorBadRequest;
 use actix_web::HttpResponse;
+use filetime::FileTime;
 use liz::liz_dbg_errs;
+use serde::Serialize;
 
+use crate::auth::User;
+use crate::conflicts::{self, Conflict};
+use crate::links::{self, SymlinkPolicy};
+use crate::perms;
+use crate::quotas;
+use crate::trash;
 use crate::SrvResult;
 use std::path::Path;
 
-pub fn list(path: &str) -> SrvResult {
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub origin: String,
+    pub destiny: String,
+    pub renamed: bool,
+    pub copied: u64,
+    pub skipped: u64,
+    pub failed: Vec<Failure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub path: String,
+    pub error: String,
+}
+
+impl Report {
+    pub fn new(origin: &str, destiny: &str) -> Self {
+        Report {
+            origin: String::from(origin),
+            destiny: String::from(destiny),
+            renamed: false,
+            copied: 0,
+            skipped: 0,
+            failed: Vec::new(),
+        }
+    }
+
+    pub fn fail(&mut self, path: &Path, error: impl std::fmt::Display) {
+        self.failed.push(Failure {
+            path: format!("{}", path.display()),
+            error: format!("{}", error),
+        });
+    }
+}
+
+pub fn list(path: &str, with_modes: bool, with_links: bool) -> SrvResult {
     let pathed = Path::new(path);
     if !pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -28,9 +72,26 @@ pub fn list(path: &str) -> SrvResult {
         let inside = entry.path();
         if let Some(name) = inside.file_name() {
             if let Some(name) = name.to_str() {
-                body.push_str(if inside.is_dir() { "D: " } else { "F: " });
+                let is_dir = if links::get_policy() == SymlinkPolicy::Deny {
+                    links::is_real_dir(&inside)
+                } else {
+                    inside.is_dir()
+                };
+                body.push_str(if is_dir { "D: " } else { "F: " });
                 body.push_str(name);
                 body.push_str("\n");
+                if with_modes {
+                    if let Some(mode) = perms::get_mode(&inside) {
+                        body.push_str(&format!("M: {:04o}\n", mode));
+                    }
+                }
+                if with_links {
+                    if let Some(target) = links::get_target(&inside) {
+                        body.push_str("L: ");
+                        body.push_str(&target);
+                        body.push_str("\n");
+                    }
+                }
             }
         }
     }
@@ -42,7 +103,13 @@ pub fn new(path: &str) -> SrvResult {
     Ok(HttpResponse::Ok().body(format!("Folder created: {}", path)))
 }
 
-pub fn copy(origin: &str, destiny: &str) -> SrvResult {
+pub fn copy(
+    origin: &str,
+    destiny: &str,
+    conflict: Option<Conflict>,
+    dry_run: bool,
+    for_user: &User,
+) -> SrvResult {
     let origin_pathed = Path::new(origin);
     if !origin_pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -56,25 +123,117 @@ pub fn copy(origin: &str, destiny: &str) -> SrvResult {
             origin
         )));
     }
-    copy_dir_all(&origin, &destiny)?;
-    Ok(HttpResponse::Ok().body(format!("Folder copied from: {} to: {}", origin, destiny)))
+    let conflict = conflict.unwrap_or(Conflict::Merge);
+    if dry_run {
+        return conflicts::run(origin, destiny, conflict, dry_run, false);
+    }
+    let _reserved = quotas::reserve_transfer(origin, destiny, false, for_user)?;
+    let tracked = quotas::track(&[destiny], for_user);
+    let result = conflicts::run(origin, destiny, conflict, dry_run, false);
+    tracked.settle();
+    result
 }
 
-fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
-    std::fs::create_dir_all(&dst)?;
-    for entry in std::fs::read_dir(src)? {
-        let entry = entry?;
-        let file_type = entry.file_type()?;
-        if file_type.is_dir() {
-            copy_dir_all(entry.path(), dst.as_ref().join(entry.file_name()))?;
+pub fn copy_dir_all(src: &Path, dst: &Path, report: &mut Report) {
+    if let Err(err) = std::fs::create_dir_all(dst) {
+        report.fail(dst, err);
+        return;
+    }
+    let entries = match std::fs::read_dir(src) {
+        Ok(entries) => entries,
+        Err(err) => {
+            report.fail(src, err);
+            return;
+        }
+    };
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                report.fail(src, err);
+                continue;
+            }
+        };
+        let inside = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(err) => {
+                report.fail(&inside, err);
+                continue;
+            }
+        };
+        if file_type.is_symlink() {
+            if let Err(err) = links::copy_link(&inside, &dst.join(entry.file_name())) {
+                report.fail(&inside, err);
+            } else {
+                report.copied += 1;
+            }
+        } else if file_type.is_dir() {
+            copy_dir_all(&inside, &dst.join(entry.file_name()), report);
+        } else if let Err(err) = copy_file_all(&inside, &dst.join(entry.file_name())) {
+            report.fail(&inside, err);
         } else {
-            std::fs::copy(entry.path(), dst.as_ref().join(entry.file_name()))?;
+            report.copied += 1;
         }
     }
+    if let Err(err) = copy_stamps(src, dst) {
+        report.fail(dst, err);
+    }
+}
+
+pub fn copy_file_all(src: &Path, dst: &Path) -> std::io::Result<()> {
+    if links::is_link(src) {
+        return links::copy_link(src, dst);
+    }
+    std::fs::copy(src, dst)?;
+    copy_stamps(src, dst)
+}
+
+fn copy_stamps(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::metadata(src)?;
+    std::fs::set_permissions(dst, metadata.permissions())?;
+    let accessed = FileTime::from_last_access_time(&metadata);
+    let modified = FileTime::from_last_modification_time(&metadata);
+    filetime::set_file_times(dst, accessed, modified)
+}
+
+pub fn is_cross_device(err: &std::io::Error) -> bool {
+    err.raw_os_error() == Some(libc::EXDEV)
+}
+
+pub fn move_all(origin: &Path, destiny: &Path, report: &mut Report) -> std::io::Result<()> {
+    match std::fs::rename(origin, destiny) {
+        Ok(()) => {
+            report.renamed = true;
+            return Ok(());
+        }
+        Err(err) => {
+            if !is_cross_device(&err) {
+                return Err(err);
+            }
+        }
+    }
+    if links::is_real_dir(origin) {
+        let failed_before = report.failed.len();
+        copy_dir_all(origin, destiny, report);
+        if report.failed.len() == failed_before {
+            std::fs::remove_dir_all(origin)?;
+        }
+    } else {
+        copy_file_all(origin, destiny)?;
+        report.copied += 1;
+        std::fs::remove_file(origin)?;
+    }
     Ok(())
 }
 
-pub fn mov(origin: &str, destiny: &str) -> SrvResult {
+pub fn mov(
+    origin: &str,
+    destiny: &str,
+    conflict: Option<Conflict>,
+    dry_run: bool,
+    for_user: &User,
+) -> SrvResult {
     let origin_pathed = Path::new(origin);
     if !origin_pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -88,12 +247,18 @@ pub fn mov(origin: &str, destiny: &str) -> SrvResult {
             origin
         )));
     }
-    copy_dir_all(origin, destiny)?;
-    std::fs::remove_dir_all(origin)?;
-    Ok(HttpResponse::Ok().body(format!("Folder moved from: {} to: {}", origin, destiny)))
+    let conflict = conflict.unwrap_or(Conflict::Merge);
+    if dry_run {
+        return conflicts::run(origin, destiny, conflict, dry_run, true);
+    }
+    let _reserved = quotas::reserve_transfer(origin, destiny, true, for_user)?;
+    let tracked = quotas::track(&[origin, destiny], for_user);
+    let result = conflicts::run(origin, destiny, conflict, dry_run, true);
+    tracked.settle();
+    result
 }
 
-pub fn del(path: &str) -> SrvResult {
+pub fn del(path: &str, to_trash: bool, for_user: &User) -> SrvResult {
     let pathed = Path::new(path);
     if !pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -107,6 +272,11 @@ pub fn del(path: &str) -> SrvResult {
             path
         )));
     }
+    if to_trash && !trash::is_in_trash(path, for_user) {
+        return trash::put(path, true, for_user);
+    }
+    let tracked = quotas::track(&[path], for_user);
     std::fs::remove_dir_all(path)?;
+    tracked.settle();
     Ok(HttpResponse::Ok().body(format!("Folder deleted: {}", path)))
 }
@@ -4,52 +4,115 @@ use actix_web::HttpResponse;
 use base64;
 use liz::liz_dbg_errs;
 
-use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::path::Path;
 
-use super::SrvResult;
+use crate::auth::User;
+use crate::conflicts::{self, Conflict};
+use crate::quotas;
+use crate::trash;
+use crate::versions;
+use crate::SrvResult;
 
 pub fn read(path: &str) -> Result<NamedFile, Error> {
     Ok(NamedFile::open(path)?)
 }
 
-pub fn write(path: &str, base64: bool, data: &str) -> SrvResult {
-    let file = OpenOptions::new()
+pub fn write(
+    path: &str,
+    base64: bool,
+    data: &str,
+    keep_versions: u64,
+    for_user: &User,
+) -> SrvResult {
+    let bytes = get_data(base64, data)?;
+    write_bytes(path, &bytes, keep_versions, for_user)?;
+    Ok(HttpResponse::Ok().body(format!("Written on: {}", path)))
+}
+
+pub fn write_bytes(
+    path: &str,
+    bytes: &[u8],
+    keep_versions: u64,
+    for_user: &User,
+) -> Result<(), Error> {
+    let existing = std::fs::metadata(path).ok().map(|metadata| metadata.len());
+    let grows = (bytes.len() as u64).saturating_sub(existing.unwrap_or(0));
+    let files = if existing.is_none() { 1 } else { 0 };
+    let reserved = quotas::reserve(path, for_user, grows, files)?;
+    versions::save(path, keep_versions)?;
+    let mut file = OpenOptions::new()
         .create(true)
         .write(true)
-        .append(false)
+        .truncate(true)
         .open(&path)?;
-    write_data(file, base64, data)?;
-    Ok(HttpResponse::Ok().body(format!("Written on: {}", path)))
+    file.write_all(bytes)?;
+    reserved.keep();
+    Ok(())
 }
 
-pub fn append(path: &str, base64: bool, data: &str) -> SrvResult {
-    let file = OpenOptions::new()
+pub fn write_staged(
+    path: &str,
+    staged: &Path,
+    keep_versions: u64,
+    for_user: &User,
+) -> Result<(), Error> {
+    let existing = std::fs::metadata(path).ok();
+    let size = std::fs::metadata(staged)?.len();
+    let grows = size.saturating_sub(existing.as_ref().map_or(0, |existing| existing.len()));
+    let files = if existing.is_none() { 1 } else { 0 };
+    let reserved = quotas::reserve(path, for_user, grows, files)?;
+    versions::save(path, keep_versions)?;
+    if let Some(existing) = existing {
+        std::fs::set_permissions(staged, existing.permissions())?;
+    }
+    std::fs::rename(staged, path)?;
+    reserved.keep();
+    Ok(())
+}
+
+pub fn append(
+    path: &str,
+    base64: bool,
+    data: &str,
+    keep_versions: u64,
+    for_user: &User,
+) -> SrvResult {
+    let bytes = get_data(base64, data)?;
+    let is_new = !Path::new(path).exists();
+    let files = if is_new { 1 } else { 0 };
+    let reserved = quotas::reserve(path, for_user, bytes.len() as u64, files)?;
+    versions::save(path, keep_versions)?;
+    let mut file = OpenOptions::new()
         .create(true)
         .write(true)
         .append(true)
         .open(&path)?;
-    write_data(file, base64, data)?;
+    file.write_all(&bytes)?;
+    reserved.keep();
     Ok(HttpResponse::Ok().body(format!("Appended on: {}", path)))
 }
 
-fn write_data(mut file: File, base64: bool, data: &str) -> Result<(), Error> {
+pub fn get_data(base64: bool, data: &str) -> Result<Vec<u8>, Error> {
     if base64 {
         let bytes = base64::decode(data);
         if let Err(err) = bytes {
             return Err(ErrorBadRequest(liz_dbg_errs!(err)));
         }
-        let bytes = bytes.unwrap();
-        file.write_all(&bytes)?;
+        Ok(bytes.unwrap())
     } else {
-        file.write_all(data.as_bytes())?;
+        Ok(data.as_bytes().to_vec())
     }
-    Ok(())
 }
 
-pub fn copy(origin: &str, destiny: &str) -> SrvResult {
+pub fn copy(
+    origin: &str,
+    destiny: &str,
+    conflict: Option<Conflict>,
+    dry_run: bool,
+    for_user: &User,
+) -> SrvResult {
     let origin_pathed = Path::new(origin);
     if !origin_pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -63,11 +126,24 @@ pub fn copy(origin: &str, destiny: &str) -> SrvResult {
             origin
         )));
     }
-    std::fs::copy(origin, destiny)?;
-    Ok(HttpResponse::Ok().body(format!("File copied from: {} to: {}", origin, destiny)))
+    let conflict = conflict.unwrap_or(Conflict::Overwrite);
+    if dry_run {
+        return conflicts::run(origin, destiny, conflict, dry_run, false);
+    }
+    let _reserved = quotas::reserve_transfer(origin, destiny, false, for_user)?;
+    let tracked = quotas::track(&[destiny], for_user);
+    let result = conflicts::run(origin, destiny, conflict, dry_run, false);
+    tracked.settle();
+    result
 }
 
-pub fn mov(origin: &str, destiny: &str) -> SrvResult {
+pub fn mov(
+    origin: &str,
+    destiny: &str,
+    conflict: Option<Conflict>,
+    dry_run: bool,
+    for_user: &User,
+) -> SrvResult {
     let origin_pathed = Path::new(origin);
     if !origin_pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -81,12 +157,18 @@ pub fn mov(origin: &str, destiny: &str) -> SrvResult {
             origin
         )));
     }
-    std::fs::copy(origin, destiny)?;
-    std::fs::remove_file(origin)?;
-    Ok(HttpResponse::Ok().body(format!("File moved from: {} to: {}", origin, destiny)))
+    let conflict = conflict.unwrap_or(Conflict::Overwrite);
+    if dry_run {
+        return conflicts::run(origin, destiny, conflict, dry_run, true);
+    }
+    let _reserved = quotas::reserve_transfer(origin, destiny, true, for_user)?;
+    let tracked = quotas::track(&[origin, destiny], for_user);
+    let result = conflicts::run(origin, destiny, conflict, dry_run, true);
+    tracked.settle();
+    result
 }
 
-pub fn del(path: &str) -> SrvResult {
+pub fn del(path: &str, to_trash: bool, for_user: &User) -> SrvResult {
     let pathed = Path::new(path);
     if !pathed.exists() {
         return Err(ErrorBadRequest(liz_dbg_errs!(
@@ -100,6 +182,11 @@ pub fn del(path: &str) -> SrvResult {
             path
         )));
     }
+    if to_trash && !trash::is_in_trash(path, for_user) {
+        return trash::put(path, false, for_user);
+    }
+    let tracked = quotas::track(&[path], for_user);
     std::fs::remove_file(&path)?;
+    tracked.settle();
     Ok(HttpResponse::Ok().body(format!("File deleted: {}", path)))
 }
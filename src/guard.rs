@@ -4,6 +4,8 @@ use liz::liz_dbg_errs;
 
 use crate::auth::{Access, User};
 use crate::base::Base;
+use crate::links::{self, SymlinkPolicy};
+use crate::trash;
 use crate::SrvData;
 
 pub fn get_user<'a>(req: &HttpRequest, srv_data: &'a SrvData) -> Option<&'a User> {
@@ -43,6 +45,11 @@ pub fn get_token_user<'a>(req: &HttpRequest, srv_data: &'a SrvData) -> Option<&'
         return None;
     }
     let found_auth = found_auth.unwrap();
+    if let Some(expires) = found_auth.expires {
+        if expires < std::time::SystemTime::now() {
+            return None;
+        }
+    }
     let user_name = &found_auth.user;
     for user in &srv_data.users {
         if user_name == &user.name {
@@ -52,6 +59,20 @@ pub fn get_token_user<'a>(req: &HttpRequest, srv_data: &'a SrvData) -> Option<&'
     None
 }
 
+pub fn get_basic_user<'a>(req: &HttpRequest, srv_data: &'a SrvData) -> Option<&'a User> {
+    let header = req.headers().get("Authorization")?.to_str().ok()?;
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = base64::decode(encoded.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let mut parts = decoded.splitn(2, ':');
+    let name = parts.next()?;
+    let pass = parts.next()?;
+    srv_data
+        .users
+        .iter()
+        .find(|user| user.name == name && user.pass == pass)
+}
+
 pub fn get_qinpel_token(req: &HttpRequest) -> String {
     if let Some(token) = req.headers().get("Qinpel-Token") {
         if let Ok(token) = token.to_str() {
@@ -88,7 +109,10 @@ pub fn check_dir_access(
     resource: &str,
     for_user: &User,
 ) -> Result<(), Error> {
-    if check_dir_resource(path_ref, &path_dest, resource, for_user) {
+    if check_dir_resource(path_ref, &path_dest, resource, for_user)
+        && check_dir_links(path_ref, path_dest, resource, for_user)
+        && check_dir_trash(path_ref, path_dest, resource, for_user)
+    {
         return Ok(());
     } else {
         return Err(ErrorForbidden(liz_dbg_errs!(
@@ -100,6 +124,76 @@ pub fn check_dir_access(
     }
 }
 
+fn check_dir_trash(
+    path_ref: &str,
+    path_dest: Option<&str>,
+    resource: &str,
+    for_user: &User,
+) -> bool {
+    let writes_ref = matches!(
+        resource,
+        "/dir/new"
+            | "/file/write"
+            | "/file/append"
+            | "/file/upload"
+            | "/file/patch"
+            | "/file/version/restore"
+            | "/path/link"
+            | "/path/chmod"
+            | "/path/chown"
+    );
+    let writes_dest = matches!(
+        resource,
+        "/dir/copy" | "/dir/move" | "/file/copy" | "/file/move"
+    );
+    if writes_ref && trash::is_in_trash(path_ref, for_user) {
+        return false;
+    }
+    if writes_dest && path_dest.map_or(false, |path_dest| trash::is_in_trash(path_dest, for_user)) {
+        return false;
+    }
+    true
+}
+
+fn check_dir_links(
+    path_ref: &str,
+    path_dest: Option<&str>,
+    resource: &str,
+    for_user: &User,
+) -> bool {
+    if for_user.master {
+        return true;
+    }
+    let on_link_itself = matches!(
+        resource,
+        "/dir/copy"
+            | "/dir/move"
+            | "/dir/del"
+            | "/file/copy"
+            | "/file/move"
+            | "/file/del"
+            | "/path/link"
+    );
+    let follow_ref = !on_link_itself;
+    let follow_dest = !on_link_itself || resource == "/path/link";
+    let linked = links::has_link_below_grant(path_ref, follow_ref, for_user)
+        || path_dest.map_or(false, |path_dest| {
+            links::has_link_below_grant(path_dest, follow_dest, for_user)
+        });
+    if !linked {
+        return true;
+    }
+    match links::get_policy() {
+        SymlinkPolicy::Deny => false,
+        SymlinkPolicy::FollowAll => true,
+        SymlinkPolicy::FollowWithinGrant => {
+            let resolved_ref = links::resolve(path_ref, follow_ref);
+            let resolved_dest = path_dest.map(|path_dest| links::resolve(path_dest, follow_dest));
+            check_dir_resource(&resolved_ref, &resolved_dest.as_deref(), resource, for_user)
+        }
+    }
+}
+
 fn check_dir_resource(
     path_ref: &str,
     path_dest: &Option<&str>,
@@ -111,6 +205,12 @@ fn check_dir_resource(
     }
     if resource == "/dir/list" {
         return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/dir/search" {
+        return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/dir/hash" {
+        return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/dir/watch" {
+        return check_dir_read(&path_ref, &for_user);
     } else if resource == "/dir/new" {
         return check_dir_write(&path_ref, &for_user);
     } else if resource == "/dir/copy" {
@@ -125,6 +225,10 @@ fn check_dir_resource(
         return check_dir_write(&path_ref, &for_user);
     } else if resource == "/file/read" {
         return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/file/hash" {
+        return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/file/tail" {
+        return check_dir_read(&path_ref, &for_user);
     } else if resource == "/file/write" {
         return check_dir_write(&path_ref, &for_user);
     } else if resource == "/file/append" {
@@ -141,6 +245,29 @@ fn check_dir_resource(
         }
     } else if resource == "/file/del" {
         return check_dir_write(&path_ref, &for_user);
+    } else if resource == "/file/diff" {
+        return check_dir_read(&path_ref, &for_user)
+            && path_dest.map_or(true, |path_dest| check_dir_read(&path_dest, &for_user));
+    } else if resource == "/file/patch" {
+        return check_dir_write(&path_ref, &for_user);
+    } else if resource == "/file/versions" {
+        return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/file/version/read" {
+        return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/file/version/restore" {
+        return check_dir_write(&path_ref, &for_user);
+    } else if resource == "/path/link" {
+        if let Some(path_dest) = path_dest {
+            return check_dir_write(&path_ref, &for_user) && check_dir_read(&path_dest, &for_user);
+        }
+    } else if resource == "/path/chmod" {
+        return check_dir_write(&path_ref, &for_user);
+    } else if resource == "/path/chown" {
+        return check_dir_write(&path_ref, &for_user);
+    } else if resource == "/share/new" {
+        return check_dir_read(&path_ref, &for_user);
+    } else if resource == "/trash/restore" {
+        return check_dir_write(&path_ref, &for_user);
     } else {
         eprintln!(
             "[SYSTEM ERROR] We got an unknown resource to check the directory access: {}",
@@ -152,7 +279,7 @@ fn check_dir_resource(
 
 pub fn check_dir_read(check_path: &str, for_user: &User) -> bool {
     for user_access in &for_user.access {
-        if let Access::DIR { path, can_write: _ } = user_access {
+        if let Access::DIR { path, .. } = user_access {
             if check_path.starts_with(path) {
                 return true;
             }
@@ -163,7 +290,7 @@ pub fn check_dir_read(check_path: &str, for_user: &User) -> bool {
 
 pub fn check_dir_write(check_path: &str, for_user: &User) -> bool {
     for user_access in &for_user.access {
-        if let Access::DIR { path, can_write } = user_access {
+        if let Access::DIR { path, can_write, .. } = user_access {
             if check_path.starts_with(path) && *can_write {
                 return true;
             }
@@ -0,0 +1,200 @@
+use actix_web::error::ErrorBadRequest;
+use actix_web::{web, HttpResponse};
+use liz::liz_dbg_errs;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::auth::User;
+use crate::bad_srv;
+use crate::guard;
+use crate::SrvResult;
+
+static CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Sha256,
+    Sha1,
+    Md5,
+    Blake3,
+}
+
+impl Default for Algorithm {
+    fn default() -> Self {
+        Algorithm::Sha256
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Hashed {
+    pub path: String,
+    pub size: u64,
+    pub hash: String,
+}
+
+enum Hasher {
+    Sha256(sha2::Sha256),
+    Sha1(sha1::Sha1),
+    Md5(md5::Md5),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            Algorithm::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            Algorithm::Md5 => Hasher::Md5(md5::Md5::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Blake3(hasher) => format!("{}", hasher.finalize().to_hex()),
+        }
+    }
+}
+
+pub fn hash_file(path: &Path, algorithm: Algorithm) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hasher.finish())
+}
+
+pub async fn file(path: &str, algorithm: Algorithm) -> SrvResult {
+    let pathed = PathBuf::from(path);
+    if !pathed.is_file() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to hash is not a file",
+            path
+        )));
+    }
+    let hashed = web::block(move || -> std::io::Result<Hashed> {
+        let size = std::fs::metadata(&pathed)?.len();
+        let hash = hash_file(&pathed, algorithm)?;
+        Ok(Hashed {
+            path: format!("{}", pathed.display()),
+            size,
+            hash,
+        })
+    })
+    .await
+    .map_err(|err| bad_srv(err))?;
+    Ok(HttpResponse::Ok().json(hashed))
+}
+
+pub async fn dir(path: &str, algorithm: Algorithm, duplicates: bool, for_user: &User) -> SrvResult {
+    let pathed = PathBuf::from(path);
+    if !pathed.is_dir() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to hash is not a directory",
+            path
+        )));
+    }
+    let user = for_user.clone();
+    if duplicates {
+        let groups = web::block(move || find_duplicates(&pathed, algorithm, &user))
+            .await
+            .map_err(|err| bad_srv(err))?;
+        return Ok(HttpResponse::Ok().json(groups));
+    }
+    let manifest = web::block(move || -> std::io::Result<Vec<Hashed>> {
+        let mut manifest = Vec::new();
+        for (inside, size) in list_files(&pathed, &user)? {
+            let hash = hash_file(&inside, algorithm)?;
+            manifest.push(Hashed {
+                path: format!("{}", inside.display()),
+                size,
+                hash,
+            });
+        }
+        Ok(manifest)
+    })
+    .await
+    .map_err(|err| bad_srv(err))?;
+    Ok(HttpResponse::Ok().json(manifest))
+}
+
+fn find_duplicates(
+    root: &Path,
+    algorithm: Algorithm,
+    for_user: &User,
+) -> std::io::Result<Vec<Vec<Hashed>>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (inside, size) in list_files(root, for_user)? {
+        by_size.entry(size).or_default().push(inside);
+    }
+    let mut by_hash: HashMap<String, Vec<Hashed>> = HashMap::new();
+    for (size, paths) in by_size {
+        if paths.len() < 2 {
+            continue;
+        }
+        for inside in paths {
+            let hash = hash_file(&inside, algorithm)?;
+            by_hash.entry(hash.clone()).or_default().push(Hashed {
+                path: format!("{}", inside.display()),
+                size,
+                hash,
+            });
+        }
+    }
+    let mut groups: Vec<Vec<Hashed>> = by_hash
+        .into_iter()
+        .map(|(_, group)| group)
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort_by(|a, b| b[0].size.cmp(&a[0].size));
+    Ok(groups)
+}
+
+fn list_files(root: &Path, for_user: &User) -> std::io::Result<Vec<(PathBuf, u64)>> {
+    let mut result = Vec::new();
+    let mut pending = vec![root.to_owned()];
+    while let Some(dir) = pending.pop() {
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            let inside = entry.path();
+            let inside_str = format!("{}", inside.display());
+            if guard::check_dir_access(&inside_str, None, "/dir/hash", for_user).is_err() {
+                continue;
+            }
+            let metadata = std::fs::symlink_metadata(&inside)?;
+            if metadata.is_dir() {
+                pending.push(inside);
+            } else if metadata.is_file() {
+                result.push((inside, metadata.len()));
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
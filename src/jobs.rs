@@ -0,0 +1,413 @@
+use actix_web::error::{Error, ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::auth::User;
+use crate::limits::{self, CmdLimits, Deadline};
+use crate::srvauth::Scoped;
+use crate::utils::{generate_id, seconds_now};
+use crate::SrvResult;
+
+static FINISHED_TTL: u64 = 60 * 60;
+static CANCEL_GRACE: Duration = Duration::from_secs(5);
+static CHUNK_SIZE: usize = 8 * 1024;
+
+static JOBS: Lazy<Mutex<HashMap<String, Job>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub owner: String,
+    pub command: String,
+    pub pid: u32,
+    pub started: u64,
+    pub finished: Option<u64>,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub cancelled: bool,
+    pub queued: bool,
+    pub timed_out: bool,
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub stdout: Captured,
+    #[serde(skip)]
+    pub stderr: Captured,
+    #[serde(skip)]
+    pub output_cap: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobOutput<'a> {
+    pub id: &'a str,
+    pub finished: bool,
+    pub stdout: &'a str,
+    pub stderr: &'a str,
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Captured {
+    pub text: String,
+    pub truncated: bool,
+}
+
+impl Captured {
+    pub fn push(&mut self, chunk: &str, cap: usize) {
+        if self.truncated {
+            return;
+        }
+        let room = cap.saturating_sub(self.text.len());
+        if chunk.len() <= room {
+            self.text.push_str(chunk);
+            return;
+        }
+        let mut end = room;
+        while !chunk.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.text.push_str(&chunk[..end]);
+        self.truncated = true;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+pub fn start(
+    mut cmd: Command,
+    cmd_name: &str,
+    inputs: &Option<Vec<String>>,
+    limits: CmdLimits,
+    output_cap: usize,
+    scoped: Scoped,
+    for_user: &User,
+) -> SrvResult {
+    let slot = if limits.queue {
+        None
+    } else {
+        Some(limits::try_acquire(cmd_name, &for_user.name, &limits)?)
+    };
+    let id = generate_id(24);
+    let job = Job {
+        id: id.clone(),
+        owner: for_user.name.clone(),
+        command: String::from(cmd_name),
+        pid: 0,
+        started: seconds_now(),
+        finished: None,
+        exit_code: None,
+        signal: None,
+        cancelled: false,
+        queued: slot.is_none(),
+        timed_out: false,
+        error: None,
+        stdout: Captured::default(),
+        stderr: Captured::default(),
+        output_cap,
+    };
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        let now = seconds_now();
+        jobs.retain(|_, job| {
+            job.finished
+                .map(|finished| now.saturating_sub(finished) < FINISHED_TTL)
+                .unwrap_or(true)
+        });
+        jobs.insert(id.clone(), job.clone());
+    }
+    let cmd_name = String::from(cmd_name);
+    let user_name = for_user.name.clone();
+    let inputs = inputs.clone();
+    std::thread::spawn(move || {
+        let slot = match slot {
+            Some(slot) => slot,
+            None => match limits::acquire(&cmd_name, &user_name, &limits) {
+                Ok(slot) => slot,
+                Err(err) => return fail(&id, err),
+            },
+        };
+        isolate(&mut cmd);
+        let spawned = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(err) => return fail(&id, err),
+        };
+        {
+            let mut jobs = JOBS.lock().unwrap();
+            let job = match jobs.get_mut(&id) {
+                Some(job) => job,
+                None => return,
+            };
+            if job.cancelled {
+                kill(child.id());
+                let _ = child.wait();
+                return;
+            }
+            job.pid = child.id();
+            job.queued = false;
+        }
+        let feeder = feed(&mut child, &inputs);
+        let deadline = limits::arm(child.id(), limits.timeout);
+        watch(child, feeder, &id, &deadline);
+        drop(slot);
+        drop(scoped);
+    });
+    Ok(HttpResponse::Ok().json(job))
+}
+
+fn fail(id: &str, err: impl std::fmt::Display) {
+    if let Some(job) = JOBS.lock().unwrap().get_mut(id) {
+        job.queued = false;
+        job.finished = Some(seconds_now());
+        job.error = Some(format!("{}", err));
+    }
+}
+
+pub type Feeder = JoinHandle<std::io::Result<()>>;
+
+pub fn feed(child: &mut Child, inputs: &Option<Vec<String>>) -> Feeder {
+    let child_stdin = child.stdin.take();
+    let inputs = inputs.clone();
+    std::thread::spawn(move || {
+        if let (Some(mut child_stdin), Some(inputs)) = (child_stdin, inputs) {
+            for input in inputs {
+                child_stdin.write_all(input.as_bytes())?;
+                child_stdin.write_all("\n".as_bytes())?;
+            }
+        }
+        Ok(())
+    })
+}
+
+pub fn fed(feeder: Feeder) -> Result<(), Error> {
+    match feeder.join() {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(ErrorInternalServerError(
+            "Could not write the inputs of the command.",
+        )),
+    }
+}
+
+fn watch(mut child: Child, feeder: Feeder, id: &str, deadline: &Deadline) {
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let stdout_id = String::from(id);
+    let stdout_reader = std::thread::spawn(move || {
+        if let Some(stdout) = stdout {
+            read_stream(stdout, |chunk| append(&stdout_id, Stream::Stdout, chunk));
+        }
+    });
+    let stderr_id = String::from(id);
+    let stderr_reader = std::thread::spawn(move || {
+        if let Some(stderr) = stderr {
+            read_stream(stderr, |chunk| append(&stderr_id, Stream::Stderr, chunk));
+        }
+    });
+    let status = child.wait();
+    deadline.finish();
+    let _ = stdout_reader.join();
+    let _ = stderr_reader.join();
+    let fed = fed(feeder);
+    if let Some(job) = JOBS.lock().unwrap().get_mut(id) {
+        job.finished = Some(seconds_now());
+        job.timed_out = deadline.timed_out();
+        if let Err(err) = fed {
+            job.error = Some(format!("{}", err));
+        }
+        match status {
+            Ok(status) => {
+                job.exit_code = status.code();
+                job.signal = get_signal(&status);
+            }
+            Err(err) => job.error = Some(format!("{}", err)),
+        }
+    }
+}
+
+pub fn read_stream(mut source: impl Read, mut on_chunk: impl FnMut(&str)) {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut pending: Vec<u8> = Vec::new();
+    loop {
+        let count = match source.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(count) => count,
+        };
+        pending.extend_from_slice(&buffer[..count]);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(_) => pending.len(),
+            Err(err) => err.valid_up_to(),
+        };
+        if valid > 0 {
+            let chunk: Vec<u8> = pending.drain(..valid).collect();
+            on_chunk(&String::from_utf8_lossy(&chunk));
+        }
+    }
+    if !pending.is_empty() {
+        on_chunk(&String::from_utf8_lossy(&pending));
+    }
+}
+
+fn append(id: &str, stream: Stream, chunk: &str) {
+    if let Some(job) = JOBS.lock().unwrap().get_mut(id) {
+        let cap = job.output_cap;
+        match stream {
+            Stream::Stdout => job.stdout.push(chunk, cap),
+            Stream::Stderr => job.stderr.push(chunk, cap),
+        }
+    }
+}
+
+pub fn status(id: &str, for_user: &User) -> SrvResult {
+    let jobs = JOBS.lock().unwrap();
+    let job = get_owned(&jobs, id, for_user)?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+pub fn output(id: &str, for_user: &User) -> SrvResult {
+    let jobs = JOBS.lock().unwrap();
+    let job = get_owned(&jobs, id, for_user)?;
+    Ok(HttpResponse::Ok().json(JobOutput {
+        id: &job.id,
+        finished: job.finished.is_some(),
+        stdout: &job.stdout.text,
+        stderr: &job.stderr.text,
+        stdout_truncated: job.stdout.truncated,
+        stderr_truncated: job.stderr.truncated,
+    }))
+}
+
+pub fn cancel(id: &str, for_user: &User) -> SrvResult {
+    let pid = {
+        let mut jobs = JOBS.lock().unwrap();
+        get_owned(&jobs, id, for_user)?;
+        let job = jobs.get_mut(id).unwrap();
+        if job.finished.is_some() {
+            return Ok(HttpResponse::Ok().body(format!("Job already finished: {}", id)));
+        }
+        job.cancelled = true;
+        if job.pid == 0 {
+            job.queued = false;
+            job.finished = Some(seconds_now());
+            return Ok(HttpResponse::Ok().body(format!("Job cancelled: {}", id)));
+        }
+        job.pid
+    };
+    terminate(pid);
+    let job_id = String::from(id);
+    std::thread::spawn(move || {
+        std::thread::sleep(CANCEL_GRACE);
+        let running = JOBS
+            .lock()
+            .unwrap()
+            .get(&job_id)
+            .map(|job| job.finished.is_none())
+            .unwrap_or(false);
+        if running {
+            kill(pid);
+        }
+    });
+    Ok(HttpResponse::Ok().body(format!("Job cancelling: {}", id)))
+}
+
+pub fn list(for_user: &User) -> SrvResult {
+    let jobs = JOBS.lock().unwrap();
+    let mut owned: Vec<&Job> = jobs
+        .values()
+        .filter(|job| job.owner == for_user.name || for_user.master)
+        .collect();
+    owned.sort_by(|a, b| a.started.cmp(&b.started));
+    Ok(HttpResponse::Ok().json(owned))
+}
+
+fn get_owned<'a>(
+    jobs: &'a HashMap<String, Job>,
+    id: &str,
+    for_user: &User,
+) -> Result<&'a Job, Error> {
+    let job = jobs
+        .get(id)
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the job", id)))?;
+    if job.owner != for_user.name && !for_user.master {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    Ok(job)
+}
+
+#[cfg(unix)]
+pub fn isolate(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(unix)]
+pub fn terminate(pid: u32) {
+    signal(pid, libc::SIGTERM);
+}
+
+#[cfg(unix)]
+pub fn kill(pid: u32) {
+    signal(pid, libc::SIGKILL);
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, signal: libc::c_int) {
+    unsafe {
+        if libc::killpg(pid as libc::pid_t, signal) != 0 {
+            libc::kill(pid as libc::pid_t, signal);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn isolate(_cmd: &mut Command) {}
+
+#[cfg(not(unix))]
+pub fn terminate(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(&["/PID", &pid.to_string(), "/T"])
+        .status();
+}
+
+#[cfg(not(unix))]
+pub fn kill(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(&["/PID", &pid.to_string(), "/T", "/F"])
+        .status();
+}
+
+#[cfg(unix)]
+pub fn get_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+pub fn get_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
@@ -17,20 +17,42 @@ use std::sync::Arc;
 
 mod auth;
 mod base;
+mod batch;
 mod body;
 mod conf;
+mod conflicts;
+mod diffs;
 mod dirs;
 mod files;
 mod guard;
+mod hashes;
+mod jobs;
+mod limits;
+mod links;
 mod lists;
+mod manifests;
+mod perms;
 mod persist;
 mod pooling;
 mod precept;
+mod quotas;
+mod sandbox;
+mod schedules;
+mod search;
+mod sessions;
+mod shares;
 mod srvauth;
 mod srvbase;
 mod srvdirs;
 mod srvruns;
 mod srvutil;
+mod streams;
+mod tail;
+mod trash;
+mod utils;
+mod versions;
+mod watch;
+mod webdav;
 
 type SrvData = web::Data<Arc<body::Body>>;
 type SrvError = actix_web::error::Error;
@@ -55,7 +77,7 @@ pub struct QinServer {
 pub async fn start(qin_server: QinServer) -> std::io::Result<()> {
     let setup = conf::Head::load(qin_server);
     let server_address = format!("{}:{}", setup.server_host, setup.server_port);
-    let body = body::Body::new(setup);
+    let body = body::Body::new(setup)?;
     if body.head.verbose {
         println!("{} starting...", body.head.server_name);
         println!("Server head: {:?}", body.head);
@@ -64,7 +86,15 @@ pub async fn start(qin_server: QinServer) -> std::io::Result<()> {
         println!("Server has {} base(s).", body.bases.len());
         println!("{:?}", body.bases);
     }
+    links::put_policy(body.head.symlink_policy);
+    quotas::put_groups(body.head.group_quotas.clone());
     let data = Arc::new(body);
+    if data.head.serves_dirs && data.head.dirs_trash && data.head.trash_retention > 0 {
+        trash::start_purger(data.clone());
+    }
+    if data.head.serves_cmds || data.head.serves_lizs {
+        schedules::start_runner(data.clone());
+    }
     let data_main = data.clone();
     let server = HttpServer::new(move || {
         let server_app = App::new();
@@ -107,19 +137,67 @@ pub async fn start(qin_server: QinServer) -> std::io::Result<()> {
                 .service(srvdirs::dir_copy)
                 .service(srvdirs::dir_move)
                 .service(srvdirs::dir_del)
+                .service(srvdirs::dir_search)
+                .service(srvdirs::dir_hash)
+                .service(srvdirs::dir_watch)
                 .service(srvdirs::file_read)
                 .service(srvdirs::file_write)
                 .service(srvdirs::file_append)
                 .service(srvdirs::file_copy)
                 .service(srvdirs::file_move)
                 .service(srvdirs::file_del)
+                .service(srvdirs::file_tail)
+                .service(srvdirs::file_hash)
+                .service(srvdirs::file_diff)
+                .service(srvdirs::file_patch)
+                .service(srvdirs::file_versions)
+                .service(srvdirs::file_version_read)
+                .service(srvdirs::file_version_restore)
+                .service(srvdirs::path_link)
+                .service(srvdirs::path_chmod)
+                .service(srvdirs::path_chown)
+                .service(srvdirs::batch_run)
+                .service(srvdirs::batch_status)
+                .service(srvdirs::me_quota)
+                .service(srvdirs::share_new)
+                .service(srvdirs::share_list)
+                .service(srvdirs::share_revoke)
+                .service(srvdirs::share_get)
+                .service(srvdirs::share_get_inside)
+                .service(srvdirs::share_post)
+                .service(srvdirs::share_post_inside)
+                .service(srvdirs::trash_list)
+                .service(srvdirs::trash_restore)
+                .service(srvdirs::trash_purge)
         } else {
             server_app
         };
+        let server_app = match (data.head.serves_dirs, &data.head.webdav_prefix) {
+            (true, Some(prefix)) => server_app
+                .service(web::resource(prefix).to(webdav::serve))
+                .service(web::resource(format!("{}/{{tail:.*}}", prefix)).to(webdav::serve)),
+            _ => server_app,
+        };
         let server_app = if data.head.serves_cmds {
             server_app
                 .service(srvruns::cmd_run)
                 .service(srvruns::list_cmds)
+                .service(srvruns::cmd_session)
+                .service(srvruns::job_status)
+                .service(srvruns::job_output)
+                .service(srvruns::job_cancel)
+                .service(srvruns::job_list)
+        } else {
+            server_app
+        };
+        let server_app = if data.head.serves_cmds || data.head.serves_lizs {
+            server_app
+                .service(srvruns::schedule_list)
+                .service(srvruns::schedule_edit)
+                .service(srvruns::schedule_del)
+                .service(srvruns::schedule_pause)
+                .service(srvruns::schedule_trigger)
+                .service(srvruns::schedule_history)
         } else {
             server_app
         };
@@ -0,0 +1,220 @@
+use actix_web::error::{Error, InternalError};
+use actix_web::http::StatusCode;
+use liz::liz_dbg_errs;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::conf::Head;
+use crate::jobs;
+
+static TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+static TIMEOUT_STEP: Duration = Duration::from_millis(100);
+
+static RUNNING: Lazy<(Mutex<HashMap<String, Instances>>, Condvar)> =
+    Lazy::new(|| (Mutex::new(HashMap::new()), Condvar::new()));
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CmdLimits {
+    pub timeout: Option<u64>,
+    pub max_output: Option<usize>,
+    pub cpu_seconds: Option<u64>,
+    pub address_space: Option<u64>,
+    pub open_files: Option<u64>,
+    /// Only applied when a sandbox runs the command under another uid, because
+    /// the kernel counts every process of the real uid, the server included.
+    pub processes: Option<u64>,
+    pub max_instances: Option<usize>,
+    pub max_user_instances: Option<usize>,
+    #[serde(default)]
+    pub queue: bool,
+}
+
+impl CmdLimits {
+    pub fn output_cap(&self, head: &Head) -> usize {
+        self.max_output.unwrap_or(head.cmd_output_cap)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Instances {
+    total: usize,
+    by_user: HashMap<String, usize>,
+}
+
+pub fn running_count(cmd_name: &str) -> usize {
+    let (running, _) = &*RUNNING;
+    running
+        .lock()
+        .unwrap()
+        .get(cmd_name)
+        .map(|instances| instances.total)
+        .unwrap_or(0)
+}
+
+pub fn get(cmd_name: &str, head: &Head) -> CmdLimits {
+    head.cmd_limits
+        .get(cmd_name)
+        .or_else(|| head.cmd_limits.get("*"))
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+pub fn apply(cmd: &mut Command, limits: &CmdLimits, run_uid: Option<u32>) {
+    use std::os::unix::process::CommandExt;
+    let own_uid = unsafe { libc::geteuid() };
+    let processes = limits
+        .processes
+        .filter(|_| run_uid.map_or(false, |run_uid| run_uid != own_uid));
+    let rlimits = vec![
+        (libc::RLIMIT_CPU, limits.cpu_seconds),
+        (libc::RLIMIT_AS, limits.address_space),
+        (libc::RLIMIT_NOFILE, limits.open_files),
+        (libc::RLIMIT_NPROC, processes),
+    ];
+    let rlimits: Vec<_> = rlimits
+        .into_iter()
+        .filter_map(|(resource, value)| value.map(|value| (resource, value as libc::rlim_t)))
+        .collect();
+    if rlimits.is_empty() {
+        return;
+    }
+    unsafe {
+        cmd.pre_exec(move || {
+            for (resource, value) in &rlimits {
+                let rlimit = libc::rlimit {
+                    rlim_cur: *value,
+                    rlim_max: *value,
+                };
+                if libc::setrlimit(*resource, &rlimit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+pub fn apply(_cmd: &mut Command, _limits: &CmdLimits, _run_uid: Option<u32>) {}
+
+pub struct Slot {
+    cmd_name: String,
+    user_name: String,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let (running, released) = &*RUNNING;
+        let mut running = running.lock().unwrap();
+        if let Some(instances) = running.get_mut(&self.cmd_name) {
+            instances.total = instances.total.saturating_sub(1);
+            if let Some(count) = instances.by_user.get_mut(&self.user_name) {
+                *count = count.saturating_sub(1);
+            }
+        }
+        released.notify_all();
+    }
+}
+
+pub fn acquire(cmd_name: &str, user_name: &str, limits: &CmdLimits) -> Result<Slot, Error> {
+    take(cmd_name, user_name, limits, limits.queue)
+}
+
+pub fn try_acquire(cmd_name: &str, user_name: &str, limits: &CmdLimits) -> Result<Slot, Error> {
+    take(cmd_name, user_name, limits, false)
+}
+
+fn take(cmd_name: &str, user_name: &str, limits: &CmdLimits, wait: bool) -> Result<Slot, Error> {
+    let (running, released) = &*RUNNING;
+    let mut running = running.lock().unwrap();
+    loop {
+        let instances = running.entry(String::from(cmd_name)).or_default();
+        let user_count = instances.by_user.get(user_name).copied().unwrap_or(0);
+        let total_full = limits
+            .max_instances
+            .map(|max| instances.total >= max)
+            .unwrap_or(false);
+        let user_full = limits
+            .max_user_instances
+            .map(|max| user_count >= max)
+            .unwrap_or(false);
+        if !total_full && !user_full {
+            instances.total += 1;
+            *instances
+                .by_user
+                .entry(String::from(user_name))
+                .or_default() += 1;
+            return Ok(Slot {
+                cmd_name: String::from(cmd_name),
+                user_name: String::from(user_name),
+            });
+        }
+        if !wait {
+            return Err(InternalError::new(
+                liz_dbg_errs!("The command reached its concurrency cap", cmd_name),
+                StatusCode::TOO_MANY_REQUESTS,
+            )
+            .into());
+        }
+        running = released.wait(running).unwrap();
+    }
+}
+
+pub struct Deadline {
+    done: Arc<AtomicBool>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    pub fn finish(&self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Relaxed)
+    }
+}
+
+pub fn arm(pid: u32, timeout: Option<u64>) -> Deadline {
+    let deadline = Deadline {
+        done: Arc::new(AtomicBool::new(false)),
+        timed_out: Arc::new(AtomicBool::new(false)),
+    };
+    if let Some(timeout) = timeout {
+        let done = deadline.done.clone();
+        let timed_out = deadline.timed_out.clone();
+        std::thread::spawn(move || {
+            let limit = Instant::now() + Duration::from_secs(timeout);
+            while Instant::now() < limit {
+                if done.load(Ordering::Relaxed) {
+                    return;
+                }
+                std::thread::sleep(TIMEOUT_STEP);
+            }
+            if done.load(Ordering::Relaxed) {
+                return;
+            }
+            timed_out.store(true, Ordering::Relaxed);
+            jobs::terminate(pid);
+            let grace = Instant::now() + TIMEOUT_GRACE;
+            while Instant::now() < grace {
+                if done.load(Ordering::Relaxed) {
+                    return;
+                }
+                std::thread::sleep(TIMEOUT_STEP);
+            }
+            if !done.load(Ordering::Relaxed) {
+                jobs::kill(pid);
+            }
+        });
+    }
+    deadline
+}
@@ -0,0 +1,168 @@
+use actix_web::error::{ErrorBadRequest, ErrorForbidden};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use once_cell::sync::OnceCell;
+
+use std::path::{Path, PathBuf};
+
+use crate::auth::{Access, User};
+use crate::SrvResult;
+
+static POLICY: OnceCell<SymlinkPolicy> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymlinkPolicy {
+    Deny,
+    FollowWithinGrant,
+    FollowAll,
+}
+
+impl Default for SymlinkPolicy {
+    fn default() -> Self {
+        SymlinkPolicy::FollowAll
+    }
+}
+
+impl SymlinkPolicy {
+    pub fn parse(source: &str) -> Option<Self> {
+        match source {
+            "deny" => Some(SymlinkPolicy::Deny),
+            "follow-within-grant" => Some(SymlinkPolicy::FollowWithinGrant),
+            "follow-all" => Some(SymlinkPolicy::FollowAll),
+            _ => None,
+        }
+    }
+}
+
+pub fn put_policy(policy: SymlinkPolicy) {
+    let _ = POLICY.set(policy);
+}
+
+pub fn get_policy() -> SymlinkPolicy {
+    POLICY.get().copied().unwrap_or_default()
+}
+
+pub fn is_link(path: &Path) -> bool {
+    std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+pub fn is_real_dir(path: &Path) -> bool {
+    std::fs::symlink_metadata(path)
+        .map(|metadata| metadata.is_dir())
+        .unwrap_or(false)
+}
+
+pub fn get_target(path: &Path) -> Option<String> {
+    std::fs::read_link(path)
+        .ok()
+        .map(|target| format!("{}", target.display()))
+}
+
+pub fn copy_link(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let target = std::fs::read_link(src)?;
+    make_link(&target, dst)
+}
+
+pub fn has_link_below_grant(check_path: &str, follow_leaf: bool, for_user: &User) -> bool {
+    let roots: Vec<&str> = for_user
+        .access
+        .iter()
+        .filter_map(|user_access| match user_access {
+            Access::DIR { path, .. } if check_path.starts_with(path.as_str()) => {
+                Some(path.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+    let skip = if follow_leaf { 0 } else { 1 };
+    for ancestor in Path::new(check_path).ancestors().skip(skip) {
+        let ancestor_str = format!("{}", ancestor.display());
+        let below = roots.iter().any(|root| ancestor_str.len() > root.len());
+        if !below {
+            break;
+        }
+        if is_link(ancestor) {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn resolve(path: &str, follow_leaf: bool) -> String {
+    let mut existing = Path::new(path);
+    let mut rest: Vec<&std::ffi::OsStr> = Vec::new();
+    if !follow_leaf {
+        if let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) {
+            rest.push(name);
+            existing = parent;
+        }
+    }
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return String::from(path),
+        }
+    }
+    let mut resolved = match existing.canonicalize() {
+        Ok(resolved) => resolved,
+        Err(_) => return String::from(path),
+    };
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    format!("{}", resolved.display())
+}
+
+pub fn link(path: &str, target: &str) -> SrvResult {
+    if get_policy() == SymlinkPolicy::Deny {
+        return Err(ErrorForbidden("The symlink policy does not allow links."));
+    }
+    let pathed = Path::new(path);
+    if is_link(pathed) || pathed.exists() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to link already exists",
+            path
+        )));
+    }
+    make_link(Path::new(target), pathed)?;
+    Ok(HttpResponse::Ok().body(format!("Linked on: {}", path)))
+}
+
+pub fn get_target_path(path: &str, target: &str) -> String {
+    let target_pathed = Path::new(target);
+    if target_pathed.is_absolute() {
+        return String::from(target);
+    }
+    let parent = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let mut joined = PathBuf::new();
+    for component in parent.join(target_pathed).components() {
+        match component {
+            std::path::Component::ParentDir => {
+                joined.pop();
+            }
+            std::path::Component::CurDir => {}
+            other => joined.push(other),
+        }
+    }
+    format!("{}", joined.display())
+}
+
+#[cfg(unix)]
+fn make_link(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn make_link(target: &Path, link: &Path) -> std::io::Result<()> {
+    let resolved = link.parent().unwrap_or_else(|| Path::new("")).join(target);
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
@@ -1,10 +1,14 @@
 use actix_web::{HttpRequest, HttpResponse};
+use liz::liz_dbg_errs;
 
 use std::path::Path;
 
 use crate::bad_srv;
 use crate::auth::Access;
 use crate::guard;
+use crate::limits;
+use crate::manifests::{self, Listed};
+use crate::precept;
 use crate::SrvData;
 use crate::SrvError;
 use crate::SrvResult;
@@ -37,28 +41,83 @@ pub fn list_apps(req: &HttpRequest, srv_data: &SrvData) -> SrvResult {
 
 pub fn list_cmds(req: &HttpRequest, srv_data: &SrvData) -> SrvResult {
 	let user = guard::get_user_or_err(req, srv_data)?;
-	let cmds_dir = liz::liz_paths::path_join(&srv_data.srv_dir, "app").map_err(|err| bad_srv(err))?;
-	let cmds_dirs = list_folder_dirs(Path::new(&cmds_dir))?;
-	let mut body = String::new();
-	if user.master {
-		cmds_dirs.into_iter().for_each(|dir| {
-			body.push_str(&dir);
-			body.push_str("\n");
+	let srv_dir = &srv_data.srv_dir;
+	let cmds_dir = liz::liz_paths::path_join(srv_dir, "run/cmd").map_err(|err| bad_srv(err))?;
+	let mut candidates = list_folder_dirs(Path::new(&cmds_dir))?;
+	for name in list_folder_execs(Path::new(srv_dir))? {
+		if !candidates.contains(&name) {
+			candidates.push(name);
+		}
+	}
+	candidates.sort();
+	let mut listed = Vec::new();
+	for name in candidates {
+		if !user.master {
+			let granted = user.access.iter().any(|user_access| match user_access {
+				Access::CMD { name: granted, args: _ } => granted == &name,
+				_ => false,
+			});
+			if !granted {
+				continue;
+			}
+		}
+		let manifest = match manifests::load(&name, srv_dir) {
+			Ok(manifest) => manifest,
+			Err(err) => {
+				eprintln!("{}", liz_dbg_errs!(err, name));
+				continue;
+			}
+		};
+		let (_, full_exec) = precept::cmd_locate(&name, srv_dir, manifest.as_ref());
+		if !full_exec.is_file() {
+			continue;
+		}
+		listed.push(Listed {
+			running: limits::running_count(&name),
+			name,
+			manifest: manifest.unwrap_or_default(),
 		});
-	} else {
-		for user_access in &user.access {
-			match user_access {
-				Access::CMD { name, args: _ } => {
-					if cmds_dirs.contains(name) {
-						body.push_str(name);
-						body.push_str("\n");
+	}
+	Ok(HttpResponse::Ok().json(listed))
+}
+
+fn list_folder_execs(folder: &Path) -> Result<Vec<String>, SrvError> {
+	let mut result = Vec::new();
+	let exe_ext = liz::liz_fires::exe_ext();
+	if folder.exists() {
+		for entry in folder.read_dir()? {
+			let entry = entry?;
+			let path = entry.path();
+			if path.is_file() && is_executable(&path) {
+				if let Some(name) = path.file_name() {
+					if let Some(name) = name.to_str() {
+						if let Some(name) = name.strip_suffix(&*exe_ext) {
+							result.push(String::from(name));
+						}
 					}
 				}
-				_ => {}
 			}
 		}
 	}
-	Ok(HttpResponse::Ok().body(body))
+	Ok(result)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+	use std::os::unix::fs::PermissionsExt;
+	path.metadata()
+		.map(|metadata| metadata.permissions().mode() & 0o111 != 0)
+		.unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+	let exe_ext = liz::liz_fires::exe_ext();
+	!exe_ext.is_empty()
+		&& path
+			.to_str()
+			.map(|path| path.ends_with(&*exe_ext))
+			.unwrap_or(false)
 }
 
 fn list_folder_dirs(folder: &Path) -> Result<Vec<String>, SrvError> {
@@ -0,0 +1,213 @@
+use actix_web::error::{Error, ErrorBadRequest};
+use liz::liz_dbg_errs;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::bad_srv;
+use crate::limits::CmdLimits;
+use crate::sandbox::Sandbox;
+
+static MANIFEST_FILE: &str = "cmd.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub args: Vec<ArgSpec>,
+    #[serde(default, skip_serializing)]
+    pub env: HashMap<String, String>,
+    pub work_dir: Option<String>,
+    #[serde(default)]
+    pub work_in_home: bool,
+    #[serde(default, skip_serializing)]
+    pub exec: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub limits: Option<CmdLimits>,
+    #[serde(default, skip_serializing)]
+    pub sandbox: Option<Sandbox>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Listed {
+    pub name: String,
+    pub running: usize,
+    #[serde(flatten)]
+    pub manifest: Manifest,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArgSpec {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub kind: ArgKind,
+    #[serde(default)]
+    pub required: bool,
+    pub values: Option<Vec<String>>,
+    pub regex: Option<String>,
+    #[serde(skip)]
+    pub pattern: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArgKind {
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+impl Default for ArgKind {
+    fn default() -> Self {
+        ArgKind::String
+    }
+}
+
+pub fn get_cmd_dir(cmd_name: &str, srv_dir: &str) -> PathBuf {
+    Path::new(srv_dir).join("run").join("cmd").join(cmd_name)
+}
+
+pub fn load(cmd_name: &str, srv_dir: &str) -> Result<Option<Manifest>, Error> {
+    let manifest_path = get_cmd_dir(cmd_name, srv_dir).join(MANIFEST_FILE);
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let manifest_file = File::open(&manifest_path)?;
+    let manifest = serde_json::from_reader(manifest_file)
+        .map_err(|err| bad_srv(liz_dbg_errs!(err, manifest_path.display())))?;
+    Ok(Some(compile(manifest)?))
+}
+
+fn compile(mut manifest: Manifest) -> Result<Manifest, Error> {
+    for spec in &mut manifest.args {
+        if let Some(regex) = &spec.regex {
+            let pattern = Regex::new(&format!("^(?:{})$", regex))
+                .map_err(|err| bad_srv(liz_dbg_errs!(err, spec.name)))?;
+            spec.pattern = Some(pattern);
+        }
+    }
+    Ok(manifest)
+}
+
+pub fn validate(manifest: &Manifest, args: &Option<Vec<String>>) -> Result<(), Error> {
+    let empty = Vec::new();
+    let args = args.as_ref().unwrap_or(&empty);
+    if args.len() > manifest.args.len() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The command received more arguments than declared",
+            args.len(),
+            manifest.args.len()
+        )));
+    }
+    for (index, spec) in manifest.args.iter().enumerate() {
+        let arg = match args.get(index) {
+            Some(arg) => arg,
+            None => {
+                if spec.required {
+                    return Err(ErrorBadRequest(liz_dbg_errs!(
+                        "The command argument is required",
+                        spec.name
+                    )));
+                }
+                continue;
+            }
+        };
+        let kind_ok = match spec.kind {
+            ArgKind::String => true,
+            ArgKind::Integer => arg.parse::<i64>().is_ok(),
+            ArgKind::Number => arg.parse::<f64>().is_ok(),
+            ArgKind::Boolean => arg == "true" || arg == "false",
+        };
+        if !kind_ok {
+            return Err(ErrorBadRequest(liz_dbg_errs!(
+                "The command argument has not the declared kind",
+                spec.name,
+                arg
+            )));
+        }
+        if let Some(values) = &spec.values {
+            if !values.contains(arg) {
+                return Err(ErrorBadRequest(liz_dbg_errs!(
+                    "The command argument is not one of the allowed values",
+                    spec.name,
+                    arg
+                )));
+            }
+        }
+        if let Some(pattern) = &spec.pattern {
+            if !pattern.is_match(arg) {
+                return Err(ErrorBadRequest(liz_dbg_errs!(
+                    "The command argument does not match the declared pattern",
+                    spec.name,
+                    arg
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_manifest() -> Manifest {
+        let manifest = serde_json::from_str(
+            r#"{
+                "args": [
+                    {"name": "mode", "required": true, "values": ["fast", "slow"]},
+                    {"name": "count", "kind": "integer"},
+                    {"name": "ratio", "kind": "number"},
+                    {"name": "dry", "kind": "boolean"},
+                    {"name": "tag", "regex": "[a-z]+-[0-9]+"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        compile(manifest).unwrap()
+    }
+
+    fn args(values: &[&str]) -> Option<Vec<String>> {
+        Some(values.iter().map(|value| String::from(*value)).collect())
+    }
+
+    #[test]
+    fn validate_accepts_declared_args() {
+        let manifest = make_manifest();
+        assert!(validate(&manifest, &args(&["fast"])).is_ok());
+        assert!(validate(&manifest, &args(&["slow", "3", "0.5", "true", "ab-12"])).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_missing_and_extra_args() {
+        let manifest = make_manifest();
+        assert!(validate(&manifest, &None).is_err());
+        assert!(validate(&manifest, &args(&["fast", "1", "1", "true", "a-1", "more"])).is_err());
+    }
+
+    #[test]
+    fn validate_checks_kinds_values_and_patterns() {
+        let manifest = make_manifest();
+        assert!(validate(&manifest, &args(&["medium"])).is_err());
+        assert!(validate(&manifest, &args(&["fast", "1.5"])).is_err());
+        assert!(validate(&manifest, &args(&["fast", "1", "abc"])).is_err());
+        assert!(validate(&manifest, &args(&["fast", "1", "1", "yes"])).is_err());
+        assert!(validate(&manifest, &args(&["fast", "1", "1", "true", "ab-12x"])).is_err());
+    }
+
+    #[test]
+    fn compile_rejects_invalid_patterns() {
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"args": [{"name": "bad", "regex": "("}]}"#).unwrap();
+        assert!(compile(manifest).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use actix_web::error::{Error, ErrorBadRequest, ErrorForbidden};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+
+use std::path::Path;
+
+use crate::auth::User;
+use crate::SrvResult;
+
+pub fn get_mode(path: &Path) -> Option<u32> {
+    get_mode_of(&std::fs::symlink_metadata(path).ok()?)
+}
+
+#[cfg(unix)]
+fn get_mode_of(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn get_mode_of(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+pub fn chmod(path: &str, mode: &str, recursive: bool, for_user: &User) -> SrvResult {
+    use std::os::unix::fs::PermissionsExt;
+    let pathed = Path::new(path);
+    if !pathed.exists() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to chmod does not exists",
+            path
+        )));
+    }
+    let mut changed = 0;
+    for target in get_targets(pathed, recursive)? {
+        let metadata = std::fs::symlink_metadata(&target)?;
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+        let current = metadata.permissions().mode() & 0o7777;
+        let updated = parse_mode(mode, current, metadata.is_dir())?;
+        if !for_user.master && updated & !current & 0o6000 != 0 {
+            return Err(ErrorForbidden(
+                "Only master users can set the setuid or setgid bits.",
+            ));
+        }
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(updated))?;
+        changed += 1;
+    }
+    Ok(HttpResponse::Ok().body(format!("Permissions changed on {} of: {}", changed, path)))
+}
+
+#[cfg(not(unix))]
+pub fn chmod(path: &str, _mode: &str, _recursive: bool, _for_user: &User) -> SrvResult {
+    Err(ErrorBadRequest(liz_dbg_errs!(
+        "Changing permissions is not supported on this platform",
+        path
+    )))
+}
+
+#[cfg(unix)]
+pub fn chown(
+    path: &str,
+    owner: Option<&str>,
+    group: Option<&str>,
+    recursive: bool,
+    for_user: &User,
+) -> SrvResult {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    if !for_user.master {
+        return Err(ErrorForbidden(
+            "Only master users can change the ownership.",
+        ));
+    }
+    let pathed = Path::new(path);
+    if !pathed.exists() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to chown does not exists",
+            path
+        )));
+    }
+    if owner.is_none() && group.is_none() {
+        return Err(ErrorBadRequest("The owner or the group must be informed."));
+    }
+    let uid = match owner {
+        Some(owner) => get_uid(owner)?,
+        None => libc::uid_t::MAX,
+    };
+    let gid = match group {
+        Some(group) => get_gid(group)?,
+        None => libc::gid_t::MAX,
+    };
+    let mut changed = 0;
+    for target in get_targets(pathed, recursive)? {
+        let target_c = CString::new(target.as_os_str().as_bytes())
+            .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, path)))?;
+        if unsafe { libc::lchown(target_c.as_ptr(), uid, gid) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        changed += 1;
+    }
+    Ok(HttpResponse::Ok().body(format!("Ownership changed on {} of: {}", changed, path)))
+}
+
+#[cfg(not(unix))]
+pub fn chown(
+    path: &str,
+    _owner: Option<&str>,
+    _group: Option<&str>,
+    _recursive: bool,
+    _for_user: &User,
+) -> SrvResult {
+    Err(ErrorBadRequest(liz_dbg_errs!(
+        "Changing ownership is not supported on this platform",
+        path
+    )))
+}
+
+#[cfg(unix)]
+fn get_uid(owner: &str) -> Result<libc::uid_t, Error> {
+    if let Ok(uid) = owner.parse::<libc::uid_t>() {
+        return Ok(uid);
+    }
+    let owner_c =
+        std::ffi::CString::new(owner).map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, owner)))?;
+    let passwd = unsafe { libc::getpwnam(owner_c.as_ptr()) };
+    if passwd.is_null() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "Could not found the system user",
+            owner
+        )));
+    }
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+#[cfg(unix)]
+fn get_gid(group: &str) -> Result<libc::gid_t, Error> {
+    if let Ok(gid) = group.parse::<libc::gid_t>() {
+        return Ok(gid);
+    }
+    let group_c =
+        std::ffi::CString::new(group).map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, group)))?;
+    let found = unsafe { libc::getgrnam(group_c.as_ptr()) };
+    if found.is_null() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "Could not found the system group",
+            group
+        )));
+    }
+    Ok(unsafe { (*found).gr_gid })
+}
+
+#[cfg(unix)]
+fn get_targets(root: &Path, recursive: bool) -> std::io::Result<Vec<std::path::PathBuf>> {
+    let mut result = vec![root.to_owned()];
+    if !recursive {
+        return Ok(result);
+    }
+    let mut pending = vec![root.to_owned()];
+    while let Some(dir) = pending.pop() {
+        if !std::fs::symlink_metadata(&dir)?.is_dir() {
+            continue;
+        }
+        for entry in dir.read_dir()? {
+            let inside = entry?.path();
+            result.push(inside.clone());
+            pending.push(inside);
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(unix)]
+fn parse_mode(mode: &str, current: u32, is_dir: bool) -> Result<u32, Error> {
+    let mode = mode.trim();
+    if !mode.is_empty() && mode.chars().all(|c| c.is_digit(8)) {
+        return u32::from_str_radix(mode, 8)
+            .ok()
+            .filter(|parsed| *parsed <= 0o7777)
+            .ok_or_else(|| ErrorBadRequest(liz_dbg_errs!("The mode is not valid", mode)));
+    }
+    let mut result = current;
+    for clause in mode.split(',') {
+        let position = clause
+            .find(|c| c == '+' || c == '-' || c == '=')
+            .ok_or_else(|| ErrorBadRequest(liz_dbg_errs!("The mode is not valid", mode)))?;
+        let (who, rest) = clause.split_at(position);
+        let operator = rest.chars().next().unwrap();
+        let mut who_mask = 0;
+        for c in who.chars() {
+            who_mask |= match c {
+                'u' => 0o4700,
+                'g' => 0o2070,
+                'o' => 0o0007,
+                'a' => 0o6777,
+                _ => {
+                    return Err(ErrorBadRequest(liz_dbg_errs!(
+                        "The mode is not valid",
+                        mode
+                    )))
+                }
+            };
+        }
+        if who_mask == 0 {
+            who_mask = 0o6777;
+        }
+        let mut bits = 0;
+        for c in rest[1..].chars() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                'X' if is_dir || current & 0o111 != 0 => 0o111,
+                'X' => 0,
+                's' => 0o6000,
+                't' => 0o1000,
+                _ => {
+                    return Err(ErrorBadRequest(liz_dbg_errs!(
+                        "The mode is not valid",
+                        mode
+                    )))
+                }
+            };
+        }
+        let bits = bits & (who_mask | if rest.contains('t') { 0o1000 } else { 0 });
+        result = match operator {
+            '+' => result | bits,
+            '-' => result & !bits,
+            _ => (result & !who_mask) | bits,
+        };
+    }
+    Ok(result)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mode_accepts_octal() {
+        assert_eq!(parse_mode("755", 0o644, false).unwrap(), 0o755);
+        assert_eq!(parse_mode("4755", 0o644, false).unwrap(), 0o4755);
+        assert!(parse_mode("17777", 0o644, false).is_err());
+    }
+
+    #[test]
+    fn parse_mode_applies_symbolic_clauses() {
+        assert_eq!(parse_mode("u+x", 0o644, false).unwrap(), 0o744);
+        assert_eq!(parse_mode("go-r", 0o644, false).unwrap(), 0o600);
+        assert_eq!(parse_mode("a=rx", 0o644, false).unwrap(), 0o555);
+        assert_eq!(parse_mode("u=rw,g+w", 0o755, false).unwrap(), 0o675);
+        assert_eq!(parse_mode("+t", 0o755, true).unwrap(), 0o1755);
+    }
+
+    #[test]
+    fn parse_mode_handles_conditional_execute() {
+        assert_eq!(parse_mode("a+X", 0o644, true).unwrap(), 0o755);
+        assert_eq!(parse_mode("a+X", 0o644, false).unwrap(), 0o644);
+        assert_eq!(parse_mode("g+X", 0o744, false).unwrap(), 0o754);
+    }
+
+    #[test]
+    fn parse_mode_scopes_special_bits() {
+        assert_eq!(parse_mode("u+s", 0o755, false).unwrap(), 0o4755);
+        assert_eq!(parse_mode("g+s", 0o755, true).unwrap(), 0o2755);
+        assert_eq!(parse_mode("o+s", 0o755, false).unwrap(), 0o755);
+    }
+
+    #[test]
+    fn parse_mode_rejects_garbage() {
+        assert!(parse_mode("", 0o644, false).is_err());
+        assert!(parse_mode("u+q", 0o644, false).is_err());
+        assert!(parse_mode("z+r", 0o644, false).is_err());
+        assert!(parse_mode("rwx", 0o644, false).is_err());
+    }
+}
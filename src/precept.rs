@@ -1,21 +1,42 @@
-use actix_web::error::{ErrorForbidden, ErrorInternalServerError};
+use actix_web::error::{Error, ErrorForbidden, ErrorInternalServerError};
+use actix_web::http::StatusCode;
 use actix_web::{HttpRequest, HttpResponse};
 use futures::executor;
 use liz::{self, liz_dbg_errs, liz_fires};
+use serde::Serialize;
 
-use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::auth::{Access, User};
+use crate::body::Body;
 use crate::guard;
+use crate::jobs::{self, Captured};
+use crate::limits::{self, CmdLimits};
+use crate::manifests::{self, Manifest};
+use crate::sandbox;
+use crate::srvauth::{self, Scoped};
 use crate::srvruns::{ArgsInputs, PathParams};
+use crate::streams;
 use crate::SrvData;
 use crate::SrvResult;
 
 static SLEEP_TO_SHUTDOWN: Duration = Duration::from_millis(1000);
 
+#[derive(Debug, Serialize)]
+pub struct CmdResult {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub stdout_truncated: bool,
+    pub stderr_truncated: bool,
+    pub timed_out: bool,
+    pub duration: u64,
+}
+
 pub fn shut(req: &HttpRequest, srv_data: &SrvData) -> SrvResult {
     if let Some(user) = guard::get_user(req, srv_data) {
         if user.master {
@@ -56,24 +77,185 @@ pub fn cmd_run(
     cmd_name: &str,
     args_inputs: &ArgsInputs,
     user: &User,
-    working_dir: &str,
+    srv_data: &Body,
 ) -> SrvResult {
+    let (cmd, limits, output_cap, scoped) = cmd_prepare(cmd_name, args_inputs, user, srv_data)?;
+    if args_inputs.job {
+        return jobs::start(
+            cmd,
+            cmd_name,
+            &args_inputs.inputs,
+            limits,
+            output_cap,
+            scoped,
+            user,
+        );
+    }
+    if args_inputs.stream {
+        return streams::stream(cmd, &args_inputs.inputs, cmd_name, &limits, scoped, user);
+    }
+    let slot = limits::try_acquire(cmd_name, &user.name, &limits)?;
+    let result = cmd_capture(cmd, &args_inputs.inputs, output_cap, limits.timeout)?;
+    drop(slot);
+    drop(scoped);
+    if args_inputs.legacy {
+        return Ok(HttpResponse::Ok().body(format!("Output: {}", result.stdout)));
+    }
+    if result.success {
+        Ok(HttpResponse::Ok().json(result))
+    } else {
+        Ok(HttpResponse::build(StatusCode::UNPROCESSABLE_ENTITY).json(result))
+    }
+}
+
+pub fn cmd_call(
+    cmd_name: &str,
+    args_inputs: &ArgsInputs,
+    user: &User,
+    srv_data: &Body,
+) -> Result<CmdResult, Error> {
+    let (cmd, limits, output_cap, _scoped) = cmd_prepare(cmd_name, args_inputs, user, srv_data)?;
+    let _slot = limits::acquire(cmd_name, &user.name, &limits)?;
+    cmd_capture(cmd, &args_inputs.inputs, output_cap, limits.timeout)
+}
+
+pub fn cmd_prepare(
+    cmd_name: &str,
+    args_inputs: &ArgsInputs,
+    user: &User,
+    srv_data: &Body,
+) -> Result<(Command, CmdLimits, usize, Scoped), Error> {
+    let head = &srv_data.head;
+    let manifest = manifests::load(cmd_name, &srv_data.srv_dir)?;
+    if let Some(manifest) = &manifest {
+        manifests::validate(manifest, &args_inputs.args)?;
+    }
+    let limits = match manifest.as_ref().and_then(|manifest| manifest.limits.clone()) {
+        Some(limits) => limits,
+        None => limits::get(cmd_name, head),
+    };
+    let output_cap = limits.output_cap(head);
+    let (mut cmd, scoped) = cmd_make(cmd_name, args_inputs, user, srv_data, manifest.as_ref())?;
+    let run_uid = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.sandbox.as_ref())
+        .and_then(sandbox::get_run_uid);
+    limits::apply(&mut cmd, &limits, run_uid);
+    Ok((cmd, limits, output_cap, scoped))
+}
+
+pub fn cmd_locate(
+    cmd_name: &str,
+    working_dir: &str,
+    manifest: Option<&Manifest>,
+) -> (PathBuf, PathBuf) {
     let working_dir = Path::new(working_dir).to_owned();
     let exec_name = format!("{}{}", cmd_name, liz_fires::exe_ext());
     let full_exec = working_dir.join(&exec_name);
-    let present_in_work_dir = full_exec.exists();
-    let working_dir = if !present_in_work_dir {
-        working_dir.join("run").join("cmd").join(cmd_name)
-    } else {
-        working_dir
+    if full_exec.is_file() {
+        return (working_dir, full_exec);
+    }
+    let working_dir = working_dir.join("run").join("cmd").join(cmd_name);
+    let full_exec = match manifest.and_then(|manifest| manifest.exec.as_ref()) {
+        Some(exec) => working_dir.join(exec),
+        None => working_dir.join(exec_name),
     };
-    let full_exec = if !present_in_work_dir {
-        working_dir.join(exec_name)
-    } else {
-        full_exec
+    (working_dir, full_exec)
+}
+
+pub fn cmd_capture(
+    mut cmd: Command,
+    inputs: &Option<Vec<String>>,
+    output_cap: usize,
+    timeout: Option<u64>,
+) -> Result<CmdResult, Error> {
+    let started = Instant::now();
+    jobs::isolate(&mut cmd);
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let feeder = jobs::feed(&mut child, inputs);
+    let deadline = limits::arm(child.id(), timeout);
+    let stdout = child.stdout.take();
+    let stdout_reader = std::thread::spawn(move || {
+        let mut captured = Captured::default();
+        if let Some(stdout) = stdout {
+            jobs::read_stream(stdout, |chunk| captured.push(chunk, output_cap));
+        }
+        captured
+    });
+    let stderr = child.stderr.take();
+    let stderr_reader = std::thread::spawn(move || {
+        let mut captured = Captured::default();
+        if let Some(stderr) = stderr {
+            jobs::read_stream(stderr, |chunk| captured.push(chunk, output_cap));
+        }
+        captured
+    });
+    let status = child.wait()?;
+    deadline.finish();
+    let stdout = stdout_reader.join().unwrap_or_default();
+    let stderr = stderr_reader.join().unwrap_or_default();
+    jobs::fed(feeder)?;
+    Ok(CmdResult {
+        success: status.success(),
+        exit_code: status.code(),
+        signal: jobs::get_signal(&status),
+        stdout: stdout.text,
+        stderr: stderr.text,
+        stdout_truncated: stdout.truncated,
+        stderr_truncated: stderr.truncated,
+        timed_out: deadline.timed_out(),
+        duration: started.elapsed().as_millis() as u64,
+    })
+}
+
+pub fn cmd_make(
+    cmd_name: &str,
+    args_inputs: &ArgsInputs,
+    user: &User,
+    srv_data: &Body,
+    manifest: Option<&Manifest>,
+) -> Result<(Command, Scoped), Error> {
+    let (working_dir, full_exec) = cmd_locate(cmd_name, &srv_data.srv_dir, manifest);
+    let work_dir = match manifest {
+        Some(manifest) if manifest.work_in_home => PathBuf::from(&user.home),
+        Some(Manifest {
+            work_dir: Some(work_dir),
+            ..
+        }) => working_dir.join(work_dir),
+        _ => working_dir.clone(),
+    };
+    let mut cmd = match manifest.and_then(|manifest| manifest.sandbox.as_ref()) {
+        Some(sandbox) => {
+            let at_root = working_dir == Path::new(&srv_data.srv_dir);
+            let exec_dir = if at_root { None } else { Some(working_dir.as_path()) };
+            let work_dir = if at_root && work_dir == working_dir {
+                Path::new("/tmp")
+            } else {
+                work_dir.as_path()
+            };
+            sandbox::wrap(&full_exec, exec_dir, work_dir, sandbox, user)?
+        }
+        None => Command::new(full_exec),
     };
-    let mut cmd = Command::new(full_exec);
-    cmd.current_dir(working_dir);
+    cmd.current_dir(&work_dir);
+    cmd.env_clear();
+    for name in &srv_data.head.cmd_env_allow {
+        if let Some(value) = std::env::var_os(name) {
+            cmd.env(name, value);
+        }
+    }
+    if let Some(manifest) = manifest {
+        cmd.envs(&manifest.env);
+    }
+    let scoped = srvauth::new_scoped_token(&user.name, srv_data.head.cmd_token_ttl, srv_data);
+    cmd.env("QINPEL_USER", &user.name);
+    cmd.env("QINPEL_HOME", &user.home);
+    cmd.env("QINPEL_LANG", &user.lang);
+    cmd.env("QINPEL_TOKEN", &scoped.token);
     for an_access in &user.access {
         if let Access::CMD { name, args } = an_access {
             if name == cmd_name {
@@ -90,17 +272,7 @@ pub fn cmd_run(
             cmd.arg(arg);
         }
     }
-    let mut child = cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
-    if let Some(inputs) = &args_inputs.inputs {
-        let child_stdin = child.stdin.as_mut().unwrap();
-        for input in inputs {
-            child_stdin.write_all(input.as_bytes())?;
-            child_stdin.write_all("\n".as_bytes())?;
-        }
-    }
-    let mut result = String::from("Output: ");
-    child.stdout.unwrap().read_to_string(&mut result)?;
-    Ok(HttpResponse::Ok().body(result))
+    Ok((cmd, scoped))
 }
 
 pub fn liz_run(path_params: &PathParams) -> SrvResult {
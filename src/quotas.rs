@@ -0,0 +1,298 @@
+use actix_web::error::{Error, InternalError};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::{Access, Quota, User};
+use crate::SrvResult;
+
+static USAGE_TTL: Duration = Duration::from_secs(10 * 60);
+
+static USAGES: Lazy<Mutex<HashMap<String, Usage>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static GROUPS: OnceCell<HashMap<String, Quota>> = OnceCell::new();
+
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    bytes: u64,
+    files: u64,
+    measured: Instant,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuotaState {
+    pub bytes_used: u64,
+    pub files_used: u64,
+    pub bytes_limit: Option<u64>,
+    pub files_limit: Option<u64>,
+}
+
+#[must_use]
+pub struct Reserved {
+    user_name: String,
+    bytes: u64,
+    files: u64,
+}
+
+impl Reserved {
+    pub fn empty() -> Self {
+        Reserved {
+            user_name: String::new(),
+            bytes: 0,
+            files: 0,
+        }
+    }
+
+    pub fn keep(mut self) {
+        self.bytes = 0;
+        self.files = 0;
+    }
+}
+
+pub struct Tracked {
+    user_name: String,
+    paths: Vec<PathBuf>,
+    bytes: u64,
+    files: u64,
+}
+
+impl Tracked {
+    pub fn settle(self) {
+        let (bytes, files) = measure_all(&self.paths);
+        let mut usages = USAGES.lock().unwrap();
+        if let Some(cached) = usages.get_mut(&self.user_name) {
+            cached.bytes = (cached.bytes + bytes).saturating_sub(self.bytes);
+            cached.files = (cached.files + files).saturating_sub(self.files);
+        }
+    }
+}
+
+impl Drop for Reserved {
+    fn drop(&mut self) {
+        if self.bytes == 0 && self.files == 0 {
+            return;
+        }
+        let mut usages = USAGES.lock().unwrap();
+        if let Some(cached) = usages.get_mut(&self.user_name) {
+            cached.bytes = cached.bytes.saturating_sub(self.bytes);
+            cached.files = cached.files.saturating_sub(self.files);
+        }
+    }
+}
+
+pub fn put_groups(groups: HashMap<String, Quota>) {
+    let _ = GROUPS.set(groups);
+}
+
+pub fn get_quota(for_user: &User) -> Option<Quota> {
+    if let Some(quota) = &for_user.quota {
+        return Some(quota.clone());
+    }
+    let groups = GROUPS.get()?;
+    let mut found: Option<Quota> = None;
+    for group in &for_user.groups {
+        if let Some(quota) = groups.get(group) {
+            found = Some(match found {
+                Some(found) => Quota {
+                    bytes: stricter(found.bytes, quota.bytes),
+                    files: stricter(found.files, quota.files),
+                },
+                None => quota.clone(),
+            });
+        }
+    }
+    found
+}
+
+fn stricter(one: Option<u64>, other: Option<u64>) -> Option<u64> {
+    match (one, other) {
+        (Some(one), Some(other)) => Some(one.min(other)),
+        (one, other) => one.or(other),
+    }
+}
+
+pub fn state(for_user: &User) -> SrvResult {
+    let usage = get_usage(for_user)?;
+    let (bytes_limit, files_limit) = match get_quota(for_user) {
+        Some(quota) => (quota.bytes, quota.files),
+        None => (None, None),
+    };
+    Ok(HttpResponse::Ok().json(QuotaState {
+        bytes_used: usage.bytes,
+        files_used: usage.files,
+        bytes_limit,
+        files_limit,
+    }))
+}
+
+pub fn reserve(path: &str, for_user: &User, bytes: u64, files: u64) -> Result<Reserved, Error> {
+    let quota = match get_quota(for_user) {
+        Some(quota) => quota,
+        None => return Ok(Reserved::empty()),
+    };
+    if !is_counted(path, for_user) {
+        return Ok(Reserved::empty());
+    }
+    let measured = get_usage(for_user)?;
+    let mut usages = USAGES.lock().unwrap();
+    let usage = usages.entry(for_user.name.clone()).or_insert(measured);
+    if usage.measured < measured.measured {
+        *usage = measured;
+    }
+    if let Some(bytes_limit) = quota.bytes {
+        if usage.bytes + bytes > bytes_limit {
+            return Err(exceeded(liz_dbg_errs!(
+                "The quota of bytes would be exceeded",
+                usage.bytes,
+                bytes,
+                bytes_limit
+            )));
+        }
+    }
+    if let Some(files_limit) = quota.files {
+        if usage.files + files > files_limit {
+            return Err(exceeded(liz_dbg_errs!(
+                "The quota of files would be exceeded",
+                usage.files,
+                files,
+                files_limit
+            )));
+        }
+    }
+    usage.bytes += bytes;
+    usage.files += files;
+    Ok(Reserved {
+        user_name: for_user.name.clone(),
+        bytes,
+        files,
+    })
+}
+
+pub fn reserve_transfer(
+    origin: &str,
+    destiny: &str,
+    moving: bool,
+    for_user: &User,
+) -> Result<Reserved, Error> {
+    if get_quota(for_user).is_none() || !is_counted(destiny, for_user) {
+        return Ok(Reserved::empty());
+    }
+    if moving && is_counted(origin, for_user) {
+        return Ok(Reserved::empty());
+    }
+    let (bytes, files) = measure(Path::new(origin))?;
+    reserve(destiny, for_user, bytes, files)
+}
+
+pub fn track(paths: &[&str], for_user: &User) -> Tracked {
+    let cached = USAGES.lock().unwrap().contains_key(&for_user.name);
+    let paths: Vec<PathBuf> = if cached && get_quota(for_user).is_some() {
+        paths
+            .iter()
+            .filter(|path| is_counted(path, for_user))
+            .map(PathBuf::from)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let (bytes, files) = measure_all(&paths);
+    Tracked {
+        user_name: for_user.name.clone(),
+        paths,
+        bytes,
+        files,
+    }
+}
+
+fn measure_all(paths: &[PathBuf]) -> (u64, u64) {
+    let mut bytes = 0;
+    let mut files = 0;
+    for path in paths {
+        if let Ok((path_bytes, path_files)) = measure(path) {
+            bytes += path_bytes;
+            files += path_files;
+        }
+    }
+    (bytes, files)
+}
+
+pub fn is_counted(path: &str, for_user: &User) -> bool {
+    get_roots(for_user)
+        .iter()
+        .any(|root| Path::new(path).starts_with(root))
+}
+
+pub fn measure(path: &Path) -> std::io::Result<(u64, u64)> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok((metadata.len(), 1));
+    }
+    let mut bytes = 0;
+    let mut files = 0;
+    for entry in path.read_dir()? {
+        let (inside_bytes, inside_files) = measure(&entry?.path())?;
+        bytes += inside_bytes;
+        files += inside_files;
+    }
+    Ok((bytes, files))
+}
+
+fn get_usage(for_user: &User) -> Result<Usage, Error> {
+    {
+        let usages = USAGES.lock().unwrap();
+        if let Some(cached) = usages.get(&for_user.name) {
+            if cached.measured.elapsed() < USAGE_TTL {
+                return Ok(*cached);
+            }
+        }
+    }
+    let mut usage = Usage {
+        bytes: 0,
+        files: 0,
+        measured: Instant::now(),
+    };
+    for root in get_roots(for_user) {
+        if root.exists() {
+            let (bytes, files) = measure(&root)?;
+            usage.bytes += bytes;
+            usage.files += files;
+        }
+    }
+    USAGES.lock().unwrap().insert(for_user.name.clone(), usage);
+    Ok(usage)
+}
+
+fn get_roots(for_user: &User) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from(&for_user.home)];
+    for user_access in &for_user.access {
+        if let Access::DIR {
+            path,
+            can_write: true,
+            ..
+        } = user_access
+        {
+            candidates.push(PathBuf::from(path));
+        }
+    }
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for candidate in &candidates {
+        let nested = candidates
+            .iter()
+            .any(|other| other != candidate && candidate.starts_with(other));
+        if !nested && !roots.contains(candidate) {
+            roots.push(candidate.clone());
+        }
+    }
+    roots
+}
+
+fn exceeded(message: String) -> Error {
+    InternalError::new(message, StatusCode::INSUFFICIENT_STORAGE).into()
+}
@@ -0,0 +1,141 @@
+use actix_web::error::Error;
+use serde::Deserialize;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::auth::{Access, User};
+
+static BWRAP: &str = "bwrap";
+static NOBODY: u32 = 65534;
+static SYSTEM_BINDS: &[&str] = &[
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc/alternatives",
+    "/etc/ld.so.cache",
+    "/etc/ld.so.conf",
+    "/etc/ssl",
+    "/etc/resolv.conf",
+    "/etc/hosts",
+    "/etc/localtime",
+];
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sandbox {
+    #[serde(default)]
+    pub drop_network: bool,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+#[cfg(target_os = "linux")]
+pub fn wrap(
+    full_exec: &Path,
+    exec_dir: Option<&Path>,
+    work_dir: &Path,
+    sandbox: &Sandbox,
+    for_user: &User,
+) -> Result<Command, Error> {
+    use std::os::unix::process::CommandExt;
+    let uid = sandbox.uid.unwrap_or(NOBODY);
+    let gid = sandbox.gid.unwrap_or(NOBODY);
+    let mut cmd = Command::new(BWRAP);
+    cmd.args(&[
+        "--die-with-parent",
+        "--new-session",
+        "--unshare-user",
+        "--unshare-pid",
+        "--unshare-ipc",
+        "--unshare-uts",
+        "--unshare-cgroup-try",
+    ]);
+    if sandbox.drop_network {
+        cmd.arg("--unshare-net");
+    }
+    cmd.arg("--uid").arg(uid.to_string());
+    cmd.arg("--gid").arg(gid.to_string());
+    for system in SYSTEM_BINDS {
+        cmd.arg("--ro-bind-try").arg(system).arg(system);
+    }
+    cmd.args(&["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"]);
+    let mut grants: Vec<(PathBuf, bool)> = for_user
+        .access
+        .iter()
+        .filter_map(|user_access| match user_access {
+            Access::DIR {
+                path, can_write, ..
+            } => Some((get_absolute(path), *can_write)),
+            _ => None,
+        })
+        .collect();
+    let home = get_absolute(&for_user.home);
+    let work_dir = get_absolute(&work_dir.to_string_lossy());
+    if work_dir == home {
+        grants.retain(|(path, _)| path != &home);
+        grants.push((home, true));
+    }
+    grants.sort_by_key(|(path, _)| path.as_os_str().len());
+    for (path, can_write) in grants {
+        let bind = if can_write {
+            "--bind-try"
+        } else {
+            "--ro-bind-try"
+        };
+        cmd.arg(bind).arg(&path).arg(&path);
+    }
+    match exec_dir {
+        Some(exec_dir) => cmd.arg("--ro-bind").arg(exec_dir).arg(exec_dir),
+        None => cmd.arg("--ro-bind").arg(full_exec).arg(full_exec),
+    };
+    cmd.arg("--chdir").arg(&work_dir);
+    cmd.arg("--").arg(full_exec);
+    if unsafe { libc::geteuid() } == 0 {
+        cmd.uid(uid);
+        cmd.gid(gid);
+    }
+    Ok(cmd)
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_run_uid(sandbox: &Sandbox) -> Option<u32> {
+    if unsafe { libc::geteuid() } == 0 {
+        Some(sandbox.uid.unwrap_or(NOBODY))
+    } else {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_absolute(path: &str) -> PathBuf {
+    let pathed = Path::new(path);
+    if pathed.is_absolute() {
+        return pathed.to_owned();
+    }
+    match std::env::current_dir() {
+        Ok(current_dir) => current_dir.join(pathed),
+        Err(_) => pathed.to_owned(),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wrap(
+    _full_exec: &Path,
+    _exec_dir: Option<&Path>,
+    _work_dir: &Path,
+    _sandbox: &Sandbox,
+    _for_user: &User,
+) -> Result<Command, Error> {
+    Err(actix_web::error::ErrorInternalServerError(
+        "The command sandbox is only supported on Linux.",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_run_uid(_sandbox: &Sandbox) -> Option<u32> {
+    None
+}
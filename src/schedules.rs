@@ -0,0 +1,425 @@
+use actix_web::error::{Error, ErrorBadRequest, ErrorForbidden, ErrorNotFound, InternalError};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use chrono::{DateTime, Local};
+use cron::Schedule as Cron;
+use liz::liz_dbg_errs;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::auth::User;
+use crate::bad_srv;
+use crate::body::Body;
+use crate::guard;
+use crate::precept;
+use crate::srvruns::ArgsInputs;
+use crate::utils::seconds_now;
+use crate::SrvResult;
+
+static SCHEDULES_FILE: &str = "schedules.json";
+static HISTORY_FILE: &str = "schedules-history.json";
+static TICK_INTERVAL: Duration = Duration::from_secs(1);
+static HISTORY_LIMIT: usize = 50;
+
+static HISTORY: Lazy<Mutex<History>> = Lazy::new(|| Mutex::new(load_history()));
+
+static HISTORY_SAVING: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+static RUNNING: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+type History = HashMap<String, VecDeque<ScheduleRun>>;
+
+pub type Schedules = Vec<Schedule>;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Schedule {
+    pub id: String,
+    pub cron: String,
+    pub user: String,
+    pub cmd: Option<String>,
+    pub liz: Option<String>,
+    pub args: Option<Vec<String>>,
+    pub inputs: Option<Vec<String>>,
+    pub params: Option<Vec<String>>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Listed {
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    pub next: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScheduleRun {
+    pub schedule: String,
+    pub manual: bool,
+    pub started: u64,
+    pub finished: u64,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub error: Option<String>,
+}
+
+struct Claim(String);
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        RUNNING.lock().unwrap().remove(&self.0);
+    }
+}
+
+pub fn load() -> std::io::Result<Schedules> {
+    let schedules_path = Path::new(SCHEDULES_FILE);
+    if schedules_path.exists() {
+        let schedules_file = File::open(schedules_path)?;
+        serde_json::from_reader(schedules_file).map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                liz_dbg_errs!("Could not parse the schedules file", err),
+            )
+        })
+    } else {
+        Ok(Schedules::new())
+    }
+}
+
+fn save(schedules: &Schedules) -> Result<(), Error> {
+    let schedules_file = File::create(SCHEDULES_FILE)?;
+    serde_json::to_writer_pretty(schedules_file, schedules).map_err(|err| bad_srv(err))
+}
+
+fn load_history() -> History {
+    let history_path = Path::new(HISTORY_FILE);
+    if !history_path.exists() {
+        return History::new();
+    }
+    let loaded = File::open(history_path)
+        .map_err(|err| liz_dbg_errs!(err, HISTORY_FILE))
+        .and_then(|history_file| {
+            serde_json::from_reader(history_file).map_err(|err| liz_dbg_errs!(err, HISTORY_FILE))
+        });
+    match loaded {
+        Ok(history) => history,
+        Err(err) => {
+            eprintln!("{}", err);
+            History::new()
+        }
+    }
+}
+
+fn save_history() {
+    let _saving = HISTORY_SAVING.lock().unwrap();
+    let history = HISTORY.lock().unwrap().clone();
+    let saved = File::create(HISTORY_FILE)
+        .map_err(|err| liz_dbg_errs!(err, HISTORY_FILE))
+        .and_then(|history_file| {
+            serde_json::to_writer(history_file, &history)
+                .map_err(|err| liz_dbg_errs!(err, HISTORY_FILE))
+        });
+    if let Err(err) = saved {
+        eprintln!("{}", err);
+    }
+}
+
+fn claim(id: &str) -> Option<Claim> {
+    if RUNNING.lock().unwrap().insert(String::from(id)) {
+        Some(Claim(String::from(id)))
+    } else {
+        None
+    }
+}
+
+pub fn start_runner(body: Arc<Body>) {
+    std::thread::spawn(move || {
+        let mut last = Local::now();
+        loop {
+            std::thread::sleep(TICK_INTERVAL);
+            let now = Local::now();
+            let schedules = body.schedules.read().unwrap().clone();
+            for schedule in schedules {
+                if schedule.paused {
+                    continue;
+                }
+                let due = match get_next(&schedule.cron, &last) {
+                    Ok(next) => next.map(|next| next <= now).unwrap_or(false),
+                    Err(err) => {
+                        eprintln!("{}", liz_dbg_errs!(err, schedule.id));
+                        false
+                    }
+                };
+                if !due {
+                    continue;
+                }
+                match claim(&schedule.id) {
+                    Some(claim) => {
+                        let body = body.clone();
+                        std::thread::spawn(move || {
+                            run(&schedule, false, &body);
+                            drop(claim);
+                        });
+                    }
+                    None => eprintln!(
+                        "{}",
+                        liz_dbg_errs!("The schedule is still running, skipped", schedule.id)
+                    ),
+                }
+            }
+            last = now;
+        }
+    });
+}
+
+fn parse_cron(cron: &str) -> Result<Cron, Error> {
+    let cron = if cron.split_whitespace().count() == 5 {
+        format!("0 {}", cron)
+    } else {
+        String::from(cron)
+    };
+    Cron::from_str(&cron).map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, cron)))
+}
+
+fn get_next(cron: &str, after: &DateTime<Local>) -> Result<Option<DateTime<Local>>, Error> {
+    Ok(parse_cron(cron)?.after(after).next())
+}
+
+fn run(schedule: &Schedule, manual: bool, body: &Body) {
+    let started = seconds_now();
+    let mut schedule_run = ScheduleRun {
+        schedule: schedule.id.clone(),
+        manual,
+        started,
+        finished: started,
+        success: false,
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        error: None,
+    };
+    if let Err(err) = execute(schedule, body, &mut schedule_run) {
+        schedule_run.success = false;
+        schedule_run.error = Some(format!("{}", err));
+    }
+    schedule_run.finished = seconds_now();
+    {
+        let mut history = HISTORY.lock().unwrap();
+        let runs = history.entry(schedule.id.clone()).or_default();
+        runs.push_back(schedule_run);
+        while runs.len() > HISTORY_LIMIT {
+            runs.pop_front();
+        }
+    }
+    save_history();
+}
+
+fn execute(schedule: &Schedule, body: &Body, schedule_run: &mut ScheduleRun) -> Result<(), Error> {
+    let user = body
+        .users
+        .iter()
+        .find(|user| user.name == schedule.user)
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the user", schedule.user)))?;
+    if let Some(cmd_name) = &schedule.cmd {
+        if !body.head.serves_cmds {
+            return Err(ErrorForbidden("The server does not serve commands."));
+        }
+        guard::check_cmd_access(cmd_name, user)?;
+        let args_inputs = ArgsInputs {
+            args: schedule.args.clone(),
+            inputs: schedule.inputs.clone(),
+            job: false,
+            stream: false,
+            legacy: false,
+        };
+        let result = precept::cmd_call(cmd_name, &args_inputs, user, body)?;
+        schedule_run.success = result.success;
+        schedule_run.exit_code = result.exit_code;
+        schedule_run.stdout = result.stdout;
+        schedule_run.stderr = result.stderr;
+    } else if let Some(liz_path) = &schedule.liz {
+        if !body.head.serves_lizs {
+            return Err(ErrorForbidden("The server does not serve Liz scripts."));
+        }
+        guard::check_liz_access(liz_path, user)?;
+        let results = liz::run(liz_path, &schedule.params)
+            .map_err(|err| bad_srv(liz_dbg_errs!(err, liz_path)))?;
+        schedule_run.success = true;
+        schedule_run.stdout = format!("[{}]", results.join(","));
+    }
+    Ok(())
+}
+
+fn check_master(for_user: &User) -> Result<(), Error> {
+    if !for_user.master {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    Ok(())
+}
+
+fn check_schedule(schedule: &Schedule, body: &Body) -> Result<(), Error> {
+    if schedule.id.is_empty() {
+        return Err(ErrorBadRequest("The schedule id was not informed."));
+    }
+    if schedule.cmd.is_some() == schedule.liz.is_some() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The schedule must point to either a command or a Liz script",
+            schedule.id
+        )));
+    }
+    if !body.users.iter().any(|user| user.name == schedule.user) {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "Could not found the user",
+            schedule.user
+        )));
+    }
+    parse_cron(&schedule.cron)?;
+    Ok(())
+}
+
+pub fn list(for_user: &User, body: &Body) -> SrvResult {
+    check_master(for_user)?;
+    let now = Local::now();
+    let schedules = body.schedules.read().unwrap();
+    let listed: Vec<Listed> = schedules
+        .iter()
+        .map(|schedule| Listed {
+            schedule: schedule.clone(),
+            next: get_next(&schedule.cron, &now)
+                .ok()
+                .flatten()
+                .map(|next| next.timestamp()),
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(listed))
+}
+
+pub fn edit(schedule: Schedule, for_user: &User, body: &Body) -> SrvResult {
+    check_master(for_user)?;
+    check_schedule(&schedule, body)?;
+    let mut schedules = body.schedules.write().unwrap();
+    match schedules.iter_mut().find(|saved| saved.id == schedule.id) {
+        Some(saved) => *saved = schedule.clone(),
+        None => schedules.push(schedule.clone()),
+    }
+    save(&schedules)?;
+    Ok(HttpResponse::Ok().json(schedule))
+}
+
+pub fn del(id: &str, for_user: &User, body: &Body) -> SrvResult {
+    check_master(for_user)?;
+    let mut schedules = body.schedules.write().unwrap();
+    let before = schedules.len();
+    schedules.retain(|schedule| schedule.id != id);
+    if schedules.len() == before {
+        return Err(ErrorNotFound(liz_dbg_errs!(
+            "Could not found the schedule",
+            id
+        )));
+    }
+    save(&schedules)?;
+    let removed = HISTORY.lock().unwrap().remove(id).is_some();
+    if removed {
+        save_history();
+    }
+    Ok(HttpResponse::Ok().body(format!("Schedule deleted: {}", id)))
+}
+
+pub fn pause(id: &str, paused: bool, for_user: &User, body: &Body) -> SrvResult {
+    check_master(for_user)?;
+    let mut schedules = body.schedules.write().unwrap();
+    let schedule = schedules
+        .iter_mut()
+        .find(|schedule| schedule.id == id)
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the schedule", id)))?;
+    schedule.paused = paused;
+    let result = schedule.clone();
+    save(&schedules)?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+pub fn trigger(id: &str, for_user: &User, body: Arc<Body>) -> SrvResult {
+    check_master(for_user)?;
+    let schedule = body
+        .schedules
+        .read()
+        .unwrap()
+        .iter()
+        .find(|schedule| schedule.id == id)
+        .cloned()
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the schedule", id)))?;
+    let claim = claim(id).ok_or_else(|| {
+        InternalError::new(
+            liz_dbg_errs!("The schedule is still running", id),
+            StatusCode::CONFLICT,
+        )
+    })?;
+    std::thread::spawn(move || {
+        run(&schedule, true, &body);
+        drop(claim);
+    });
+    Ok(HttpResponse::Ok().body(format!("Schedule triggered: {}", id)))
+}
+
+pub fn history(id: &str, for_user: &User) -> SrvResult {
+    check_master(for_user)?;
+    let history = HISTORY.lock().unwrap();
+    let runs: Vec<&ScheduleRun> = history
+        .get(id)
+        .map(|runs| runs.iter().rev().collect())
+        .unwrap_or_default();
+    Ok(HttpResponse::Ok().json(runs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    #[test]
+    fn parse_cron_accepts_five_and_six_fields() {
+        assert!(parse_cron("*/5 * * * *").is_ok());
+        assert!(parse_cron("30 0 12 * * *").is_ok());
+    }
+
+    #[test]
+    fn parse_cron_rejects_garbage() {
+        assert!(parse_cron("").is_err());
+        assert!(parse_cron("every minute").is_err());
+        assert!(parse_cron("61 * * * *").is_err());
+    }
+
+    #[test]
+    fn get_next_runs_after_the_given_time() {
+        let after = DateTime::parse_from_rfc3339("2024-03-10T08:07:30Z")
+            .unwrap()
+            .with_timezone(&Local);
+        let next = get_next("*/15 * * * *", &after).unwrap().unwrap();
+        assert!(next > after);
+        assert!(next - after <= chrono::Duration::minutes(15));
+        assert_eq!((next.minute() % 15, next.second()), (0, 0));
+        let next = get_next("0 9 * * *", &after).unwrap().unwrap();
+        assert!(next > after);
+        assert_eq!((next.hour(), next.minute()), (9, 0));
+    }
+
+    #[test]
+    fn claim_blocks_overlapping_runs() {
+        let first = claim("claim-test");
+        assert!(first.is_some());
+        assert!(claim("claim-test").is_none());
+        drop(first);
+        assert!(claim("claim-test").is_some());
+    }
+}
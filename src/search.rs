@@ -0,0 +1,240 @@
+use actix_web::error::{Error, ErrorBadRequest};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use std::fs::{File, Metadata};
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::auth::User;
+use crate::guard;
+use crate::srvdirs::PathSearch;
+use crate::utils::{event_channel, send_bytes, EventSender};
+use crate::SrvResult;
+
+static BINARY_PROBE: usize = 8 * 1024;
+static DEFAULT_MAX_RESULTS: usize = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct Found {
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: u64,
+    pub line: Option<u64>,
+}
+
+struct Matcher {
+    name: Option<Regex>,
+    content: Option<Regex>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    after: Option<u64>,
+    before: Option<u64>,
+    max_depth: Option<usize>,
+    max_results: usize,
+}
+
+pub fn search(path: &str, params: &PathSearch, for_user: &User) -> SrvResult {
+    let pathed = Path::new(path);
+    if !pathed.is_dir() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to search is not a directory",
+            path
+        )));
+    }
+    let matcher = Matcher::new(params)?;
+    let root = pathed.to_owned();
+    let user = for_user.clone();
+    let (mut sender, receiver) = event_channel();
+    std::thread::spawn(move || {
+        let mut found = 0;
+        walk(&root, 0, &matcher, &user, &mut sender, &mut found);
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .streaming(receiver))
+}
+
+fn walk(
+    dir: &Path,
+    depth: usize,
+    matcher: &Matcher,
+    for_user: &User,
+    sender: &mut EventSender,
+    found: &mut usize,
+) -> bool {
+    let entries = match dir.read_dir() {
+        Ok(entries) => entries,
+        Err(_) => return true,
+    };
+    for entry in entries {
+        if sender.is_closed() {
+            return false;
+        }
+        let inside = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        let metadata = match std::fs::symlink_metadata(&inside) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let inside_str = format!("{}", inside.display());
+        if guard::check_dir_access(&inside_str, None, "/dir/search", for_user).is_err() {
+            continue;
+        }
+        if let Some(got) = matcher.check(&inside, &metadata) {
+            let mut line = serde_json::to_vec(&got).unwrap_or_default();
+            line.push(b'\n');
+            if !send_bytes(sender, line) {
+                return false;
+            }
+            *found += 1;
+            if *found >= matcher.max_results {
+                return false;
+            }
+        }
+        if metadata.is_dir() && matcher.max_depth.map(|max| depth < max).unwrap_or(true) {
+            if !walk(&inside, depth + 1, matcher, for_user, sender, found) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+impl Matcher {
+    fn new(params: &PathSearch) -> Result<Self, Error> {
+        let name = if let Some(name_regex) = &params.name_regex {
+            Some(build_regex(name_regex, params.ignore_case)?)
+        } else if let Some(name_glob) = &params.name_glob {
+            Some(build_regex(&glob_to_regex(name_glob), params.ignore_case)?)
+        } else {
+            None
+        };
+        let content = if let Some(content_regex) = &params.content_regex {
+            Some(build_regex(content_regex, params.ignore_case)?)
+        } else if let Some(content_text) = &params.content_text {
+            Some(build_regex(
+                &regex::escape(content_text),
+                params.ignore_case,
+            )?)
+        } else {
+            None
+        };
+        Ok(Matcher {
+            name,
+            content,
+            min_size: params.min_size,
+            max_size: params.max_size,
+            after: params.after,
+            before: params.before,
+            max_depth: params.max_depth,
+            max_results: params.max_results.unwrap_or(DEFAULT_MAX_RESULTS),
+        })
+    }
+
+    fn check(&self, path: &Path, metadata: &Metadata) -> Option<Found> {
+        if let Some(name) = &self.name {
+            let file_name = path.file_name()?.to_string_lossy();
+            if !name.is_match(&file_name) {
+                return None;
+            }
+        }
+        let size = metadata.len();
+        if self.min_size.map(|min| size < min).unwrap_or(false) {
+            return None;
+        }
+        if self.max_size.map(|max| size > max).unwrap_or(false) {
+            return None;
+        }
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        if self.after.map(|after| modified < after).unwrap_or(false) {
+            return None;
+        }
+        if self.before.map(|before| modified > before).unwrap_or(false) {
+            return None;
+        }
+        let mut line = None;
+        if let Some(content) = &self.content {
+            if !metadata.is_file() {
+                return None;
+            }
+            line = Some(find_content(path, content)?);
+        }
+        Some(Found {
+            path: format!("{}", path.display()),
+            is_dir: metadata.is_dir(),
+            size,
+            modified,
+            line,
+        })
+    }
+}
+
+fn find_content(path: &Path, content: &Regex) -> Option<u64> {
+    let mut file = File::open(path).ok()?;
+    let mut probe = vec![0u8; BINARY_PROBE];
+    let probed = file.read(&mut probe).ok()?;
+    if probe[..probed].contains(&0) {
+        return None;
+    }
+    let reader = BufReader::new(File::open(path).ok()?);
+    for (index, line) in reader.split(b'\n').enumerate() {
+        let line = line.ok()?;
+        if content.is_match(&String::from_utf8_lossy(&line)) {
+            return Some(index as u64 + 1);
+        }
+    }
+    None
+}
+
+fn build_regex(source: &str, ignore_case: bool) -> Result<Regex, Error> {
+    RegexBuilder::new(source)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, source)))
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut result = String::from("^");
+    for part in glob.chars() {
+        match part {
+            '*' => result.push_str(".*"),
+            '?' => result.push('.'),
+            _ => result.push_str(&regex::escape(&part.to_string())),
+        }
+    }
+    result.push('$');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_to_regex_anchors_and_translates_wildcards() {
+        assert_eq!(glob_to_regex("*.rs"), "^.*\\.rs$");
+        assert_eq!(glob_to_regex("a?c"), "^a.c$");
+    }
+
+    #[test]
+    fn glob_to_regex_matches_names() {
+        let regex = build_regex(&glob_to_regex("*.TXT"), true).unwrap();
+        assert!(regex.is_match("notes.txt"));
+        assert!(!regex.is_match("notes.txt.bak"));
+        let regex = build_regex(&glob_to_regex("file(1)+.md"), false).unwrap();
+        assert!(regex.is_match("file(1)+.md"));
+        assert!(!regex.is_match("file1.md"));
+    }
+}
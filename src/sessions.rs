@@ -0,0 +1,416 @@
+use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, Message, StreamHandler};
+use actix_web::error::{Error, ErrorBadRequest};
+use actix_web_actors::ws;
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::auth::User;
+use crate::body::Body;
+use crate::jobs::{self, Stream};
+use crate::limits;
+use crate::precept;
+use crate::srvruns::ArgsInputs;
+
+static DEFAULT_ROWS: u16 = 24;
+static DEFAULT_COLS: u16 = 80;
+static INPUT_BUFFER: usize = 64;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Incoming {
+    Start {
+        args: Option<Vec<String>>,
+        #[serde(default)]
+        pty: bool,
+        rows: Option<u16>,
+        cols: Option<u16>,
+    },
+    Input {
+        data: String,
+    },
+    Resize {
+        rows: u16,
+        cols: u16,
+    },
+    Eof,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum Outgoing<'a> {
+    Stdout {
+        data: &'a str,
+    },
+    Stderr {
+        data: &'a str,
+    },
+    Exit {
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        timed_out: bool,
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Output {
+    stream: Stream,
+    data: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Finished {
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    timed_out: bool,
+}
+
+pub struct Session {
+    cmd_name: String,
+    user: User,
+    body: Arc<Body>,
+    stdin: Option<SyncSender<Vec<u8>>>,
+    pty: Option<File>,
+    pid: Option<u32>,
+    finished: bool,
+}
+
+impl Session {
+    pub fn new(cmd_name: &str, user: User, body: Arc<Body>) -> Self {
+        Session {
+            cmd_name: String::from(cmd_name),
+            user,
+            body,
+            stdin: None,
+            pty: None,
+            pid: None,
+            finished: false,
+        }
+    }
+
+    fn start(
+        &mut self,
+        args: Option<Vec<String>>,
+        pty: bool,
+        rows: u16,
+        cols: u16,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<(), Error> {
+        if self.pid.is_some() {
+            return Err(ErrorBadRequest("The session command was already started."));
+        }
+        let args_inputs = ArgsInputs {
+            args,
+            inputs: None,
+            job: false,
+            stream: false,
+            legacy: false,
+        };
+        let (mut cmd, limits, _, scoped) =
+            precept::cmd_prepare(&self.cmd_name, &args_inputs, &self.user, &self.body)?;
+        let slot = limits::try_acquire(&self.cmd_name, &self.user.name, &limits)?;
+        let addr = ctx.address();
+        let mut readers = Vec::new();
+        let mut child = if pty {
+            let (master, slave) = open_pty(rows, cols)?;
+            attach_pty(&mut cmd, slave)?;
+            let child = cmd.spawn()?;
+            drop(cmd);
+            readers.push(relay(master.try_clone()?, Stream::Stdout, addr.clone()));
+            self.stdin = Some(feed(master.try_clone()?));
+            self.pty = Some(master);
+            child
+        } else {
+            jobs::isolate(&mut cmd);
+            let mut child = cmd
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            if let Some(stdout) = child.stdout.take() {
+                readers.push(relay(stdout, Stream::Stdout, addr.clone()));
+            }
+            if let Some(stderr) = child.stderr.take() {
+                readers.push(relay(stderr, Stream::Stderr, addr.clone()));
+            }
+            if let Some(stdin) = child.stdin.take() {
+                self.stdin = Some(feed(stdin));
+            }
+            child
+        };
+        let pid = child.id();
+        self.pid = Some(pid);
+        let deadline = limits::arm(pid, limits.timeout);
+        std::thread::spawn(move || {
+            let status = child.wait();
+            deadline.finish();
+            drop(slot);
+            drop(scoped);
+            for reader in readers {
+                let _ = reader.join();
+            }
+            let (exit_code, signal) = match status {
+                Ok(status) => (status.code(), jobs::get_signal(&status)),
+                Err(_) => (None, None),
+            };
+            addr.do_send(Finished {
+                exit_code,
+                signal,
+                timed_out: deadline.timed_out(),
+            });
+        });
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8], ctx: &mut ws::WebsocketContext<Self>) -> Result<(), Error> {
+        let stdin = self
+            .stdin
+            .as_ref()
+            .ok_or_else(|| ErrorBadRequest("The session command is not accepting input."))?;
+        match stdin.try_send(data.to_vec()) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                let message = String::from("The session command is not reading its input.");
+                self.send(&Outgoing::Error { message }, ctx);
+                ctx.close(None);
+                ctx.stop();
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.stdin = None;
+                Err(ErrorBadRequest(
+                    "The session command is not accepting input.",
+                ))
+            }
+        }
+    }
+
+    fn close_input(&mut self, ctx: &mut ws::WebsocketContext<Self>) -> Result<(), Error> {
+        if self.pty.is_some() {
+            self.write(b"\x04", ctx)
+        } else {
+            self.stdin = None;
+            Ok(())
+        }
+    }
+
+    fn receive(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) -> Result<(), Error> {
+        let incoming: Incoming =
+            serde_json::from_str(text).map_err(|err| ErrorBadRequest(format!("{}", err)))?;
+        match incoming {
+            Incoming::Start {
+                args,
+                pty,
+                rows,
+                cols,
+            } => self.start(
+                args,
+                pty,
+                rows.unwrap_or(DEFAULT_ROWS),
+                cols.unwrap_or(DEFAULT_COLS),
+                ctx,
+            ),
+            Incoming::Input { data } => self.write(data.as_bytes(), ctx),
+            Incoming::Resize { rows, cols } => match &self.pty {
+                Some(master) => resize_pty(master, rows, cols),
+                None => Err(ErrorBadRequest("The session has no pseudo-terminal.")),
+            },
+            Incoming::Eof => self.close_input(ctx),
+        }
+    }
+
+    fn send(&self, outgoing: &Outgoing, ctx: &mut ws::WebsocketContext<Self>) {
+        if let Ok(text) = serde_json::to_string(outgoing) {
+            ctx.text(text);
+        }
+    }
+}
+
+impl Actor for Session {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        if !self.finished {
+            if let Some(pid) = self.pid {
+                jobs::kill(pid);
+            }
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Session {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        let done = match msg {
+            Ok(ws::Message::Ping(bytes)) => {
+                ctx.pong(&bytes);
+                Ok(())
+            }
+            Ok(ws::Message::Text(text)) => self.receive(&text, ctx),
+            Ok(ws::Message::Binary(bytes)) => self.write(&bytes, ctx),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+                Ok(())
+            }
+            Ok(_) => Ok(()),
+            Err(_) => {
+                ctx.stop();
+                Ok(())
+            }
+        };
+        if let Err(err) = done {
+            let message = format!("{}", err);
+            self.send(&Outgoing::Error { message }, ctx);
+        }
+    }
+}
+
+impl Handler<Output> for Session {
+    type Result = ();
+
+    fn handle(&mut self, output: Output, ctx: &mut Self::Context) {
+        let outgoing = match output.stream {
+            Stream::Stdout => Outgoing::Stdout { data: &output.data },
+            Stream::Stderr => Outgoing::Stderr { data: &output.data },
+        };
+        self.send(&outgoing, ctx);
+    }
+}
+
+impl Handler<Finished> for Session {
+    type Result = ();
+
+    fn handle(&mut self, finished: Finished, ctx: &mut Self::Context) {
+        self.finished = true;
+        self.stdin = None;
+        self.pty = None;
+        let outgoing = Outgoing::Exit {
+            exit_code: finished.exit_code,
+            signal: finished.signal,
+            timed_out: finished.timed_out,
+        };
+        self.send(&outgoing, ctx);
+        ctx.close(None);
+        ctx.stop();
+    }
+}
+
+fn feed(mut sink: impl Write + Send + 'static) -> SyncSender<Vec<u8>> {
+    let (sender, receiver) = sync_channel::<Vec<u8>>(INPUT_BUFFER);
+    std::thread::spawn(move || {
+        for data in receiver {
+            if sink.write_all(&data).and_then(|_| sink.flush()).is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+fn relay(
+    source: impl Read + Send + 'static,
+    stream: Stream,
+    addr: Addr<Session>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || {
+        jobs::read_stream(source, |chunk| {
+            addr.do_send(Output {
+                stream,
+                data: String::from(chunk),
+            })
+        });
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn open_pty(rows: u16, cols: u16) -> Result<(File, File), Error> {
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    unsafe {
+        let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if master < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let master = File::from_raw_fd(master);
+        let master_fd = master.as_raw_fd();
+        if libc::grantpt(master_fd) != 0 || libc::unlockpt(master_fd) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut name = [0 as libc::c_char; 128];
+        if libc::ptsname_r(master_fd, name.as_mut_ptr(), name.len()) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let slave = libc::open(name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY);
+        if slave < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let slave = File::from_raw_fd(slave);
+        resize_pty(&master, rows, cols)?;
+        Ok((master, slave))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn attach_pty(cmd: &mut Command, slave: File) -> Result<(), Error> {
+    use std::os::unix::process::CommandExt;
+    cmd.stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn resize_pty(master: &File, rows: u16, cols: u16) -> Result<(), Error> {
+    use std::os::unix::io::AsRawFd;
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_pty(_rows: u16, _cols: u16) -> Result<(File, File), Error> {
+    Err(ErrorBadRequest(
+        "The pseudo-terminal is only supported on Linux.",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn attach_pty(_cmd: &mut Command, _slave: File) -> Result<(), Error> {
+    Err(ErrorBadRequest(
+        "The pseudo-terminal is only supported on Linux.",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resize_pty(_master: &File, _rows: u16, _cols: u16) -> Result<(), Error> {
+    Err(ErrorBadRequest(
+        "The pseudo-terminal is only supported on Linux.",
+    ))
+}
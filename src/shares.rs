@@ -0,0 +1,265 @@
+use actix_files::NamedFile;
+use actix_web::error::{Error, ErrorBadRequest, ErrorForbidden, ErrorGone, ErrorNotFound};
+use actix_web::{HttpRequest, HttpResponse};
+use hmac::Hmac;
+use liz::liz_dbg_errs;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use std::fs::File;
+use std::path::{Component, Path};
+
+use crate::auth::User;
+use crate::bad_srv;
+use crate::guard;
+use crate::srvdirs::ShareNew;
+use crate::utils::{generate_id, seconds_now};
+use crate::SrvData;
+use crate::SrvResult;
+
+static SHARES_FILE: &str = "shares.json";
+static SALT_SIZE: usize = 16;
+static HASH_ROUNDS: u32 = 100_000;
+static HASH_SIZE: usize = 32;
+
+pub type Shares = Vec<Share>;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Share {
+    pub id: String,
+    pub owner: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub created: u64,
+    pub expires: Option<u64>,
+    pub pass: Option<String>,
+    #[serde(default)]
+    pub salt: Option<String>,
+    pub max_downloads: Option<u64>,
+    pub downloads: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Shared {
+    pub id: String,
+    pub link: String,
+}
+
+pub fn load() -> std::io::Result<Shares> {
+    let shares_path = Path::new(SHARES_FILE);
+    if shares_path.exists() {
+        let shares_file = File::open(shares_path)?;
+        serde_json::from_reader(shares_file).map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                liz_dbg_errs!("Could not parse the shares file", err),
+            )
+        })
+    } else {
+        Ok(Shares::new())
+    }
+}
+
+fn save(shares: &Shares) -> Result<(), Error> {
+    let shares_file = File::create(SHARES_FILE)?;
+    serde_json::to_writer_pretty(shares_file, shares).map_err(|err| bad_srv(err))
+}
+
+pub fn new(path: &str, params: &ShareNew, for_user: &User, srv_data: &SrvData) -> SrvResult {
+    let pathed = Path::new(path);
+    if !pathed.exists() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to share does not exists",
+            path
+        )));
+    }
+    let now = seconds_now();
+    let salt = generate_id(SALT_SIZE);
+    let share = Share {
+        id: generate_id(24),
+        owner: for_user.name.clone(),
+        path: String::from(path),
+        is_dir: pathed.is_dir(),
+        created: now,
+        expires: params.expires_in.map(|expires_in| now + expires_in),
+        pass: params.pass.as_ref().map(|pass| hash_pass(pass, &salt)),
+        salt: params.pass.as_ref().map(|_| salt),
+        max_downloads: params.max_downloads,
+        downloads: 0,
+    };
+    let shared = Shared {
+        id: share.id.clone(),
+        link: format!("/s/{}", share.id),
+    };
+    let mut shares = srv_data.shares.write().unwrap();
+    shares.retain(|share| !is_expired(share, now));
+    shares.push(share);
+    save(&shares)?;
+    Ok(HttpResponse::Ok().json(shared))
+}
+
+pub fn list(for_user: &User, srv_data: &SrvData) -> SrvResult {
+    let now = seconds_now();
+    let shares = srv_data.shares.read().unwrap();
+    let owned: Vec<Share> = shares
+        .iter()
+        .filter(|share| share.owner == for_user.name || for_user.master)
+        .filter(|share| !is_expired(share, now))
+        .map(|share| Share {
+            pass: share.pass.as_ref().map(|_| String::from("*")),
+            salt: None,
+            ..share.clone()
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(owned))
+}
+
+pub fn revoke(id: &str, for_user: &User, srv_data: &SrvData) -> SrvResult {
+    let mut shares = srv_data.shares.write().unwrap();
+    let position = shares
+        .iter()
+        .position(|share| share.id == id && (share.owner == for_user.name || for_user.master))
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the share", id)))?;
+    shares.remove(position);
+    save(&shares)?;
+    Ok(HttpResponse::Ok().body(format!("Share revoked: {}", id)))
+}
+
+pub fn get(
+    id: &str,
+    inside: &str,
+    pass: Option<&str>,
+    req: &HttpRequest,
+    srv_data: &SrvData,
+) -> SrvResult {
+    let share = srv_data
+        .shares
+        .read()
+        .unwrap()
+        .iter()
+        .find(|share| share.id == id)
+        .cloned()
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the share", id)))?;
+    if is_expired(&share, seconds_now()) {
+        return Err(ErrorGone(liz_dbg_errs!("The share has expired", id)));
+    }
+    if let Some(share_pass) = &share.pass {
+        let matches = match (pass, &share.salt) {
+            (Some(pass), Some(salt)) => {
+                let hashed = hash_pass(pass, salt);
+                bool::from(hashed.as_bytes().ct_eq(share_pass.as_bytes()))
+            }
+            _ => false,
+        };
+        if !matches {
+            return Err(ErrorForbidden("The share password does not match"));
+        }
+    }
+    let owner = srv_data
+        .users
+        .iter()
+        .find(|user| user.name == share.owner)
+        .ok_or_else(|| ErrorGone(liz_dbg_errs!("The share owner no longer exists", id)))?;
+    let mut target = Path::new(&share.path).to_owned();
+    if !inside.is_empty() {
+        if !share.is_dir {
+            return Err(ErrorNotFound(liz_dbg_errs!(
+                "The share is not a directory",
+                id,
+                inside
+            )));
+        }
+        let inside_pathed = Path::new(inside);
+        if inside_pathed
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+        {
+            return Err(ErrorBadRequest(liz_dbg_errs!(
+                "The path inside the share is not valid",
+                inside
+            )));
+        }
+        target = target.join(inside_pathed);
+    }
+    let target_str = format!("{}", target.display());
+    let resource = if target.is_dir() {
+        "/dir/list"
+    } else {
+        "/file/read"
+    };
+    guard::check_dir_access(&target_str, None, resource, owner)?;
+    if target.is_dir() {
+        return list_names(&target);
+    }
+    count_download(id, srv_data)?;
+    Ok(NamedFile::open(target)?.into_response(req)?)
+}
+
+fn count_download(id: &str, srv_data: &SrvData) -> Result<(), Error> {
+    let mut shares = srv_data.shares.write().unwrap();
+    let share = shares
+        .iter_mut()
+        .find(|share| share.id == id)
+        .ok_or_else(|| ErrorNotFound(liz_dbg_errs!("Could not found the share", id)))?;
+    if is_expired(share, seconds_now()) {
+        return Err(ErrorGone(liz_dbg_errs!("The share has expired", id)));
+    }
+    share.downloads += 1;
+    save(&shares)
+}
+
+fn list_names(dir: &Path) -> SrvResult {
+    let mut body = String::new();
+    for entry in dir.read_dir()? {
+        let inside = entry?.path();
+        if let Some(name) = inside.file_name() {
+            if let Some(name) = name.to_str() {
+                body.push_str(if inside.is_dir() { "D: " } else { "F: " });
+                body.push_str(name);
+                body.push_str("\n");
+            }
+        }
+    }
+    Ok(HttpResponse::Ok().body(body))
+}
+
+fn is_expired(share: &Share, now: u64) -> bool {
+    if let Some(expires) = share.expires {
+        if now > expires {
+            return true;
+        }
+    }
+    if let Some(max_downloads) = share.max_downloads {
+        if share.downloads >= max_downloads {
+            return true;
+        }
+    }
+    false
+}
+
+fn hash_pass(pass: &str, salt: &str) -> String {
+    let mut hashed = [0u8; HASH_SIZE];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(pass.as_bytes(), salt.as_bytes(), HASH_ROUNDS, &mut hashed);
+    base64::encode(hashed)
+}
+
+pub fn get_pass(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("Qinpel-Share-Pass")
+        .and_then(|pass| pass.to_str().ok())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_pass_is_salted() {
+        let first = hash_pass("secret", "salt-one");
+        assert_eq!(first, hash_pass("secret", "salt-one"));
+        assert_ne!(first, hash_pass("secret", "salt-two"));
+        assert_ne!(first, hash_pass("other", "salt-one"));
+    }
+}
@@ -5,7 +5,11 @@ use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
 use crate::auth::{Authed, User};
+use crate::body::Body;
 use crate::guard;
 use crate::SrvData;
 use crate::SrvResult;
@@ -44,6 +48,7 @@ pub async fn enter(auth: Json<TryAuth>, srv_data: SrvData) -> SrvResult {
         let auth = Authed {
             user: auth.name.clone(),
             from: std::time::SystemTime::now(),
+            expires: None,
         };
         {
             srv_data.tokens.write().unwrap().insert(token, auth);
@@ -68,6 +73,37 @@ pub async fn exit(req: HttpRequest, srv_data: SrvData) -> SrvResult {
 
 static CLEAN_INTERVAL: u64 = 24 * 60 * 60;
 
+pub struct Scoped {
+    pub token: String,
+    tokens: Arc<RwLock<HashMap<String, Authed>>>,
+}
+
+impl Drop for Scoped {
+    fn drop(&mut self) {
+        self.tokens.write().unwrap().remove(&self.token);
+    }
+}
+
+pub fn new_scoped_token(user_name: &str, ttl: u64, srv_data: &Body) -> Scoped {
+    let token = generate_token();
+    let now = std::time::SystemTime::now();
+    let auth = Authed {
+        user: String::from(user_name),
+        from: now,
+        expires: Some(now + std::time::Duration::from_secs(ttl)),
+    };
+    let mut tokens = srv_data.tokens.write().unwrap();
+    tokens.retain(|_, auth| match auth.expires {
+        Some(expires) => expires > now,
+        None => true,
+    });
+    tokens.insert(token.clone(), auth);
+    Scoped {
+        token,
+        tokens: srv_data.tokens.clone(),
+    }
+}
+
 fn try_clean_tokens(srv_data: SrvData) {
     liz_dbg_call!(srv_data);
     let elapsed = { srv_data.last_clean.elapsed().unwrap().as_secs() };
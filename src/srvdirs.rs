@@ -1,12 +1,25 @@
 use actix_files::NamedFile;
 use actix_web::error::{Error, ErrorBadRequest, ErrorForbidden};
-use actix_web::{post, web::Json, HttpRequest};
+use actix_web::{get, post, web::Json, web::Path, web::Query, HttpRequest};
 use liz::{liz_dbg_errs, liz_paths};
 use serde::Deserialize;
 
+use crate::batch::{self, Batch};
+use crate::conflicts::Conflict;
+use crate::diffs;
 use crate::dirs;
 use crate::files;
 use crate::guard;
+use crate::hashes::{self, Algorithm};
+use crate::links;
+use crate::perms;
+use crate::quotas;
+use crate::search;
+use crate::shares;
+use crate::tail;
+use crate::trash;
+use crate::versions;
+use crate::watch;
 use crate::SrvData;
 use crate::SrvResult;
 
@@ -15,10 +28,131 @@ pub struct OnePath {
     pub path: String,
 }
 
+#[derive(Deserialize)]
+pub struct PathList {
+    pub path: String,
+    #[serde(default)]
+    pub modes: bool,
+    #[serde(default)]
+    pub links: bool,
+}
+
 #[derive(Deserialize)]
 pub struct TwoPath {
     pub origin: String,
     pub destiny: String,
+    pub conflict: Option<Conflict>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PathWatch {
+    pub path: String,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PathHash {
+    pub path: String,
+    #[serde(default)]
+    pub algorithm: Algorithm,
+    #[serde(default)]
+    pub duplicates: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PathTail {
+    pub path: String,
+    pub lines: Option<usize>,
+    #[serde(default)]
+    pub follow: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PathSearch {
+    pub path: String,
+    pub name_glob: Option<String>,
+    pub name_regex: Option<String>,
+    pub content_text: Option<String>,
+    pub content_regex: Option<String>,
+    #[serde(default)]
+    pub ignore_case: bool,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub max_results: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct PathDiff {
+    pub path: String,
+    pub other: Option<String>,
+    pub data: Option<String>,
+    #[serde(default)]
+    pub base64: bool,
+    pub context: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct PathPatch {
+    pub path: String,
+    pub patch: String,
+}
+
+#[derive(Deserialize)]
+pub struct PathVersion {
+    pub path: String,
+    pub id: u64,
+}
+
+#[derive(Deserialize)]
+pub struct PathLink {
+    pub path: String,
+    pub target: String,
+}
+
+#[derive(Deserialize)]
+pub struct PathMode {
+    pub path: String,
+    pub mode: String,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PathOwner {
+    pub path: String,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ShareNew {
+    pub path: String,
+    pub expires_in: Option<u64>,
+    pub pass: Option<String>,
+    pub max_downloads: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct ShareId {
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+pub struct SharePass {
+    pub pass: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TrashId {
+    pub id: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -29,7 +163,7 @@ pub struct PathData {
 }
 
 #[post("/dir/list")]
-pub async fn dir_list(one: Json<OnePath>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+pub async fn dir_list(rec: Json<PathList>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
     let user = guard::get_user(&req, &srv_data);
     if user.is_none() {
         return Err(ErrorForbidden(
@@ -37,14 +171,71 @@ pub async fn dir_list(one: Json<OnePath>, req: HttpRequest, srv_data: SrvData) -
         ));
     }
     let user = user.unwrap();
-    let path = match liz_paths::path_join_if_relative(&user.home, &one.path) {
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
         Ok(path) => path,
         Err(err) => {
-            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &one.path)));
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
         }
     };
     guard::check_dir_access(&path, None, "/dir/list", &user)?;
-    dirs::list(&path)
+    dirs::list(&path, rec.modes, rec.links)
+}
+
+#[post("/dir/search")]
+pub async fn dir_search(rec: Json<PathSearch>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/dir/search", &user)?;
+    search::search(&path, &rec, &user)
+}
+
+#[post("/dir/hash")]
+pub async fn dir_hash(rec: Json<PathHash>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/dir/hash", &user)?;
+    hashes::dir(&path, rec.algorithm, rec.duplicates, &user).await
+}
+
+#[get("/dir/watch")]
+pub async fn dir_watch(rec: Query<PathWatch>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/dir/watch", &user)?;
+    watch::watch(&path, rec.recursive)
 }
 
 #[post("/dir/new")]
@@ -92,7 +283,7 @@ pub async fn dir_copy(two: Json<TwoPath>, req: HttpRequest, srv_data: SrvData) -
         }
     };
     guard::check_dir_access(&origin, Some(&destiny), "/dir/copy", &user)?;
-    dirs::copy(&origin, &destiny)
+    dirs::copy(&origin, &destiny, two.conflict, two.dry_run, &user)
 }
 
 #[post("/dir/move")]
@@ -121,7 +312,7 @@ pub async fn dir_move(two: Json<TwoPath>, req: HttpRequest, srv_data: SrvData) -
         }
     };
     guard::check_dir_access(&origin, Some(&destiny), "/dir/move", &user)?;
-    dirs::mov(&origin, &destiny)
+    dirs::mov(&origin, &destiny, two.conflict, two.dry_run, &user)
 }
 
 #[post("/dir/del")]
@@ -140,7 +331,7 @@ pub async fn dir_del(one: Json<OnePath>, req: HttpRequest, srv_data: SrvData) ->
         }
     };
     guard::check_dir_access(&path, None, "/dir/del", &user)?;
-    dirs::del(&path)
+    dirs::del(&path, srv_data.head.dirs_trash, &user)
 }
 
 #[post("/file/read")]
@@ -166,6 +357,44 @@ pub async fn file_read(
     files::read(&path)
 }
 
+#[post("/file/hash")]
+pub async fn file_hash(rec: Json<PathHash>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/file/hash", &user)?;
+    hashes::file(&path, rec.algorithm).await
+}
+
+#[get("/file/tail")]
+pub async fn file_tail(rec: Query<PathTail>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/file/tail", &user)?;
+    tail::tail(&path, rec.lines, rec.follow)
+}
+
 #[post("/file/write")]
 pub async fn file_write(rec: Json<PathData>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
     let user = guard::get_user(&req, &srv_data);
@@ -182,7 +411,8 @@ pub async fn file_write(rec: Json<PathData>, req: HttpRequest, srv_data: SrvData
         }
     };
     guard::check_dir_access(&path, None, "/file/write", &user)?;
-    files::write(&path, rec.base64, &rec.data)
+    let keep_versions = versions::get_limit(&path, &user, &srv_data.head);
+    files::write(&path, rec.base64, &rec.data, keep_versions, &user)
 }
 
 #[post("/file/append")]
@@ -201,7 +431,8 @@ pub async fn file_append(rec: Json<PathData>, req: HttpRequest, srv_data: SrvDat
         }
     };
     guard::check_dir_access(&path, None, "/file/append", &user)?;
-    files::append(&path, rec.base64, &rec.data)
+    let keep_versions = versions::get_limit(&path, &user, &srv_data.head);
+    files::append(&path, rec.base64, &rec.data, keep_versions, &user)
 }
 
 #[post("/file/copy")]
@@ -230,7 +461,7 @@ pub async fn file_copy(two: Json<TwoPath>, req: HttpRequest, srv_data: SrvData)
         }
     };
     guard::check_dir_access(&origin, Some(&destiny), "/file/copy", &user)?;
-    files::copy(&origin, &destiny)
+    files::copy(&origin, &destiny, two.conflict, two.dry_run, &user)
 }
 
 #[post("/file/move")]
@@ -259,7 +490,7 @@ pub async fn file_move(two: Json<TwoPath>, req: HttpRequest, srv_data: SrvData)
         }
     };
     guard::check_dir_access(&origin, Some(&destiny), "/file/move", &user)?;
-    files::mov(&origin, &destiny)
+    files::mov(&origin, &destiny, two.conflict, two.dry_run, &user)
 }
 
 #[post("/file/del")]
@@ -278,5 +509,346 @@ pub async fn file_del(one: Json<OnePath>, req: HttpRequest, srv_data: SrvData) -
         }
     };
     guard::check_dir_access(&path, None, "/file/del", &user)?;
-    files::del(&path)
+    files::del(&path, srv_data.head.dirs_trash, &user)
+}
+
+#[post("/file/diff")]
+pub async fn file_diff(rec: Json<PathDiff>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    let other = match &rec.other {
+        Some(other) => match liz_paths::path_join_if_relative(&user.home, other) {
+            Ok(other) => Some(other),
+            Err(err) => {
+                return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, other)));
+            }
+        },
+        None => None,
+    };
+    guard::check_dir_access(&path, other.as_deref(), "/file/diff", &user)?;
+    diffs::diff(&path, other.as_deref(), &rec)
+}
+
+#[post("/file/patch")]
+pub async fn file_patch(rec: Json<PathPatch>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/file/patch", &user)?;
+    let keep_versions = versions::get_limit(&path, &user, &srv_data.head);
+    diffs::patch(&path, &rec.patch, keep_versions, &user)
+}
+
+#[post("/file/versions")]
+pub async fn file_versions(one: Json<OnePath>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &one.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &one.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/file/versions", &user)?;
+    versions::list(&path)
+}
+
+#[post("/file/version/read")]
+pub async fn file_version_read(
+    rec: Json<PathVersion>,
+    req: HttpRequest,
+    srv_data: SrvData,
+) -> Result<NamedFile, Error> {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/file/version/read", &user)?;
+    versions::read(&path, rec.id)
+}
+
+#[post("/file/version/restore")]
+pub async fn file_version_restore(
+    rec: Json<PathVersion>,
+    req: HttpRequest,
+    srv_data: SrvData,
+) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/file/version/restore", &user)?;
+    let keep_versions = versions::get_limit(&path, &user, &srv_data.head);
+    versions::restore(&path, rec.id, keep_versions, &user)
+}
+
+#[post("/batch")]
+pub async fn batch_run(rec: Json<Batch>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    batch::run(rec.into_inner(), &user, srv_data.get_ref().clone())
+}
+
+#[get("/batch/{id}")]
+pub async fn batch_status(id: Path<String>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    batch::status(&id, &user)
+}
+
+#[post("/path/link")]
+pub async fn path_link(rec: Json<PathLink>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    let target = links::get_target_path(&path, &rec.target);
+    guard::check_dir_access(&path, Some(&target), "/path/link", &user)?;
+    links::link(&path, &rec.target)
+}
+
+#[post("/path/chmod")]
+pub async fn path_chmod(rec: Json<PathMode>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/path/chmod", &user)?;
+    perms::chmod(&path, &rec.mode, rec.recursive, &user)
+}
+
+#[post("/path/chown")]
+pub async fn path_chown(rec: Json<PathOwner>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/path/chown", &user)?;
+    perms::chown(
+        &path,
+        rec.owner.as_deref(),
+        rec.group.as_deref(),
+        rec.recursive,
+        &user,
+    )
+}
+
+#[get("/me/quota")]
+pub async fn me_quota(req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    quotas::state(&user)
+}
+
+#[post("/share/new")]
+pub async fn share_new(rec: Json<ShareNew>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let path = match liz_paths::path_join_if_relative(&user.home, &rec.path) {
+        Ok(path) => path,
+        Err(err) => {
+            return Err(ErrorBadRequest(liz_dbg_errs!(err, &user.home, &rec.path)));
+        }
+    };
+    guard::check_dir_access(&path, None, "/share/new", &user)?;
+    shares::new(&path, &rec, &user, &srv_data)
+}
+
+#[get("/share/list")]
+pub async fn share_list(req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    shares::list(&user, &srv_data)
+}
+
+#[post("/share/revoke")]
+pub async fn share_revoke(rec: Json<ShareId>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    shares::revoke(&rec.id, &user, &srv_data)
+}
+
+#[get("/s/{id}")]
+pub async fn share_get(id: Path<String>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let pass = shares::get_pass(&req);
+    shares::get(&id, "", pass.as_deref(), &req, &srv_data)
+}
+
+#[get("/s/{id}/{inside:.*}")]
+pub async fn share_get_inside(
+    path: Path<(String, String)>,
+    req: HttpRequest,
+    srv_data: SrvData,
+) -> SrvResult {
+    let (id, inside) = path.into_inner();
+    let pass = shares::get_pass(&req);
+    shares::get(&id, &inside, pass.as_deref(), &req, &srv_data)
+}
+
+#[post("/s/{id}")]
+pub async fn share_post(
+    id: Path<String>,
+    rec: Json<SharePass>,
+    req: HttpRequest,
+    srv_data: SrvData,
+) -> SrvResult {
+    shares::get(&id, "", rec.pass.as_deref(), &req, &srv_data)
+}
+
+#[post("/s/{id}/{inside:.*}")]
+pub async fn share_post_inside(
+    path: Path<(String, String)>,
+    rec: Json<SharePass>,
+    req: HttpRequest,
+    srv_data: SrvData,
+) -> SrvResult {
+    let (id, inside) = path.into_inner();
+    shares::get(&id, &inside, rec.pass.as_deref(), &req, &srv_data)
+}
+
+#[get("/trash/list")]
+pub async fn trash_list(req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    trash::list(&user)
+}
+
+#[post("/trash/restore")]
+pub async fn trash_restore(
+    rec: Json<TrashId>,
+    req: HttpRequest,
+    srv_data: SrvData,
+) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    let id = match &rec.id {
+        Some(id) => id,
+        None => {
+            return Err(ErrorBadRequest("The trash identifier was not informed."));
+        }
+    };
+    trash::restore(id, &user)
+}
+
+#[post("/trash/purge")]
+pub async fn trash_purge(rec: Json<TrashId>, req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    let user = guard::get_user(&req, &srv_data);
+    if user.is_none() {
+        return Err(ErrorForbidden(
+            "You do not have access to call this resource.",
+        ));
+    }
+    let user = user.unwrap();
+    trash::purge(&rec.id, &user)
 }
@@ -1,13 +1,17 @@
 use actix_files::NamedFile;
 use actix_web::error::{Error, ErrorBadRequest};
-use actix_web::{get, post, web::Json, HttpRequest};
+use actix_web::{get, post, web, web::Json, HttpRequest};
+use actix_web_actors::ws;
 use liz::{liz_dbg_call, liz_dbg_errs, liz_dbg_reav, liz_dbg_step, liz_paths};
 use serde::Deserialize;
 
 use crate::bad_req;
 use crate::guard;
+use crate::jobs;
 use crate::lists;
 use crate::precept;
+use crate::schedules::{self, Schedule};
+use crate::sessions;
 use crate::SrvData;
 use crate::SrvResult;
 
@@ -15,6 +19,28 @@ use crate::SrvResult;
 pub struct ArgsInputs {
     pub args: Option<Vec<String>>,
     pub inputs: Option<Vec<String>>,
+    #[serde(default)]
+    pub job: bool,
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default)]
+    pub legacy: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobId {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleId {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchedulePause {
+    pub id: String,
+    pub paused: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -87,10 +113,64 @@ pub async fn cmd_run(
         cmd_name,
         &args_inputs,
         &user,
-        &srv_data.srv_dir
+        &srv_data
     ))
 }
 
+#[get("/session/*")]
+pub async fn cmd_session(
+    req: HttpRequest,
+    payload: web::Payload,
+    srv_data: SrvData,
+) -> SrvResult {
+    liz_dbg_call!(req, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    let path = req.match_info().path();
+    liz_dbg_step!(path);
+    let cmd_name = path
+        .split("/")
+        .nth(2)
+        .ok_or("Could not found the command name")
+        .map_err(|err| bad_req(err))?;
+    liz_dbg_step!(cmd_name);
+    guard::check_cmd_access(cmd_name, user)?;
+    let session = sessions::Session::new(cmd_name, user.clone(), srv_data.get_ref().clone());
+    liz_dbg_reav!(ws::start(session, &req, payload))
+}
+
+#[post("/job/status")]
+pub async fn job_status(req: HttpRequest, job_id: Json<JobId>, srv_data: SrvData) -> SrvResult {
+    liz_dbg_call!(req, job_id, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(jobs::status(&job_id.id, &user))
+}
+
+#[post("/job/output")]
+pub async fn job_output(req: HttpRequest, job_id: Json<JobId>, srv_data: SrvData) -> SrvResult {
+    liz_dbg_call!(req, job_id, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(jobs::output(&job_id.id, &user))
+}
+
+#[post("/job/cancel")]
+pub async fn job_cancel(req: HttpRequest, job_id: Json<JobId>, srv_data: SrvData) -> SrvResult {
+    liz_dbg_call!(req, job_id, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(jobs::cancel(&job_id.id, &user))
+}
+
+#[get("/job/list")]
+pub async fn job_list(req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    liz_dbg_call!(req, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(jobs::list(&user))
+}
+
 #[get("/list/cmds")]
 pub async fn list_cmds(req: HttpRequest, srv_data: SrvData) -> SrvResult {
     liz_dbg_call!(req, srv_data);
@@ -109,3 +189,80 @@ pub async fn liz_run(
     guard::check_liz_access(&path_params.path, &user)?;
     liz_dbg_reav!(precept::liz_run(&path_params));
 }
+
+#[get("/schedule/list")]
+pub async fn schedule_list(req: HttpRequest, srv_data: SrvData) -> SrvResult {
+    liz_dbg_call!(req, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(schedules::list(&user, &srv_data))
+}
+
+#[post("/schedule/edit")]
+pub async fn schedule_edit(
+    req: HttpRequest,
+    schedule: Json<Schedule>,
+    srv_data: SrvData,
+) -> SrvResult {
+    liz_dbg_call!(req, schedule, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(schedules::edit(schedule.into_inner(), &user, &srv_data))
+}
+
+#[post("/schedule/del")]
+pub async fn schedule_del(
+    req: HttpRequest,
+    schedule_id: Json<ScheduleId>,
+    srv_data: SrvData,
+) -> SrvResult {
+    liz_dbg_call!(req, schedule_id, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(schedules::del(&schedule_id.id, &user, &srv_data))
+}
+
+#[post("/schedule/pause")]
+pub async fn schedule_pause(
+    req: HttpRequest,
+    schedule_pause: Json<SchedulePause>,
+    srv_data: SrvData,
+) -> SrvResult {
+    liz_dbg_call!(req, schedule_pause, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(schedules::pause(
+        &schedule_pause.id,
+        schedule_pause.paused,
+        &user,
+        &srv_data
+    ))
+}
+
+#[post("/schedule/trigger")]
+pub async fn schedule_trigger(
+    req: HttpRequest,
+    schedule_id: Json<ScheduleId>,
+    srv_data: SrvData,
+) -> SrvResult {
+    liz_dbg_call!(req, schedule_id, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(schedules::trigger(
+        &schedule_id.id,
+        &user,
+        srv_data.get_ref().clone()
+    ))
+}
+
+#[post("/schedule/history")]
+pub async fn schedule_history(
+    req: HttpRequest,
+    schedule_id: Json<ScheduleId>,
+    srv_data: SrvData,
+) -> SrvResult {
+    liz_dbg_call!(req, schedule_id, srv_data);
+    let user = guard::get_user_or_err(&req, &srv_data)?;
+    liz_dbg_step!(user);
+    liz_dbg_reav!(schedules::history(&schedule_id.id, &user))
+}
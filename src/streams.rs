@@ -0,0 +1,108 @@
+use actix_web::HttpResponse;
+use serde::Serialize;
+
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::User;
+use crate::jobs;
+use crate::limits::{self, CmdLimits};
+use crate::srvauth::Scoped;
+use crate::utils::{event_channel, send_bytes, send_event};
+use crate::SrvResult;
+
+static KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Serialize)]
+pub struct Exited {
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub timed_out: bool,
+}
+
+pub fn stream(
+    mut cmd: Command,
+    inputs: &Option<Vec<String>>,
+    cmd_name: &str,
+    limits: &CmdLimits,
+    scoped: Scoped,
+    for_user: &User,
+) -> SrvResult {
+    let slot = limits::try_acquire(cmd_name, &for_user.name, limits)?;
+    jobs::isolate(&mut cmd);
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let feeder = jobs::feed(&mut child, inputs);
+    let deadline = limits::arm(child.id(), limits.timeout);
+    let (mut sender, receiver) = event_channel();
+    let pid = child.id();
+    let stdout = child.stdout.take();
+    let mut stdout_sender = sender.clone();
+    let stdout_reader = std::thread::spawn(move || {
+        if let Some(stdout) = stdout {
+            jobs::read_stream(stdout, |chunk| {
+                if !send_event(&mut stdout_sender, "stdout", chunk) {
+                    jobs::kill(pid);
+                }
+            });
+        }
+    });
+    let stderr = child.stderr.take();
+    let mut stderr_sender = sender.clone();
+    let stderr_reader = std::thread::spawn(move || {
+        if let Some(stderr) = stderr {
+            jobs::read_stream(stderr, |chunk| {
+                if !send_event(&mut stderr_sender, "stderr", chunk) {
+                    jobs::kill(pid);
+                }
+            });
+        }
+    });
+    let running = Arc::new(AtomicBool::new(true));
+    let alive_running = running.clone();
+    let mut alive_sender = sender.clone();
+    std::thread::spawn(move || {
+        while alive_running.load(Ordering::Relaxed) {
+            std::thread::sleep(KEEP_ALIVE);
+            if !send_bytes(&mut alive_sender, ": alive\n\n") {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || {
+        let status = child.wait();
+        deadline.finish();
+        drop(slot);
+        drop(scoped);
+        let _ = stdout_reader.join();
+        let _ = stderr_reader.join();
+        if let Err(err) = jobs::fed(feeder) {
+            send_event(&mut sender, "error", &format!("{}", err));
+        }
+        running.store(false, Ordering::Relaxed);
+        let exited = match status {
+            Ok(status) => Exited {
+                exit_code: status.code(),
+                signal: jobs::get_signal(&status),
+                timed_out: deadline.timed_out(),
+            },
+            Err(_) => Exited {
+                exit_code: None,
+                signal: None,
+                timed_out: deadline.timed_out(),
+            },
+        };
+        let exited = serde_json::to_string(&exited).unwrap_or_default();
+        send_event(&mut sender, "exit", &exited);
+        sender.close_channel();
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(receiver))
+}
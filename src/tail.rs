@@ -0,0 +1,215 @@
+use actix_web::error::{Error, ErrorBadRequest};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::utils::{event_channel, send_bytes, send_event, EventSender};
+use crate::SrvResult;
+
+static DEFAULT_LINES: usize = 10;
+static MAX_LINES: usize = 10_000;
+static CHUNK_SIZE: u64 = 8 * 1024;
+static MAX_TAIL_BYTES: u64 = 16 * 1024 * 1024;
+static MAX_PENDING: usize = 64 * 1024;
+static POLL_INTERVAL: Duration = Duration::from_millis(500);
+static KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+pub fn tail(path: &str, lines: Option<usize>, follow: bool) -> SrvResult {
+    let pathed = Path::new(path);
+    if !pathed.is_file() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to tail is not a file",
+            path
+        )));
+    }
+    let lines = lines.unwrap_or(DEFAULT_LINES).min(MAX_LINES);
+    let (last_lines, offset) = read_last_lines(pathed, lines)?;
+    if !follow {
+        let mut body = last_lines.join("\n");
+        if !body.is_empty() {
+            body.push('\n');
+        }
+        return Ok(HttpResponse::Ok().body(body));
+    }
+    let (mut sender, receiver) = event_channel();
+    let pathed = pathed.to_owned();
+    std::thread::spawn(move || {
+        for line in &last_lines {
+            if !send_event(&mut sender, "line", line) {
+                return;
+            }
+        }
+        follow_file(pathed, offset, sender)
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(receiver))
+}
+
+fn read_last_lines(path: &Path, lines: usize) -> Result<(Vec<String>, u64), Error> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+    let mut start = length;
+    let mut chunks: Vec<Vec<u8>> = Vec::new();
+    let mut newlines = 0;
+    while start > 0 && newlines <= lines && length - start < MAX_TAIL_BYTES {
+        let step = CHUNK_SIZE.min(start);
+        start -= step;
+        file.seek(SeekFrom::Start(start))?;
+        let mut chunk = vec![0u8; step as usize];
+        file.read_exact(&mut chunk)?;
+        newlines += chunk.iter().filter(|byte| **byte == b'\n').count();
+        chunks.push(chunk);
+    }
+    if length == 0 || lines == 0 {
+        return Ok((Vec::new(), length));
+    }
+    chunks.reverse();
+    let buffer = chunks.concat();
+    let text = String::from_utf8_lossy(&buffer);
+    let all: Vec<&str> = text.trim_end_matches('\n').split('\n').collect();
+    let skip = all.len().saturating_sub(lines);
+    let result = all[skip..]
+        .iter()
+        .map(|line| String::from(line.trim_end_matches('\r')))
+        .collect();
+    Ok((result, length))
+}
+
+fn follow_file(path: PathBuf, mut offset: u64, mut sender: EventSender) {
+    let mut identity = std::fs::metadata(&path)
+        .ok()
+        .map(|metadata| get_identity(&metadata));
+    let mut pending: Vec<u8> = Vec::new();
+    let mut last_sent = Instant::now();
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if sender.is_closed() {
+            break;
+        }
+        let metadata = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        let current = get_identity(&metadata);
+        if identity.is_some() && identity != Some(current) {
+            offset = 0;
+            pending.clear();
+            if !send_event(&mut sender, "rotated", "") {
+                break;
+            }
+        } else if metadata.len() < offset {
+            offset = 0;
+            pending.clear();
+            if !send_event(&mut sender, "truncated", "") {
+                break;
+            }
+        }
+        identity = Some(current);
+        if metadata.len() > offset {
+            while offset < metadata.len() {
+                let bytes = match read_from(&path, offset) {
+                    Ok(bytes) if !bytes.is_empty() => bytes,
+                    _ => break,
+                };
+                offset += bytes.len() as u64;
+                pending.extend_from_slice(&bytes);
+                while let Some(position) = pending.iter().position(|byte| *byte == b'\n') {
+                    let line: Vec<u8> = pending.drain(..=position).collect();
+                    let line = String::from_utf8_lossy(&line[..line.len() - 1]);
+                    if !send_event(&mut sender, "line", line.trim_end_matches('\r')) {
+                        return;
+                    }
+                }
+                if pending.len() > MAX_PENDING {
+                    let line: Vec<u8> = pending.drain(..).collect();
+                    if !send_event(&mut sender, "line", &String::from_utf8_lossy(&line)) {
+                        return;
+                    }
+                }
+            }
+            last_sent = Instant::now();
+        } else if last_sent.elapsed() > KEEP_ALIVE {
+            if !send_bytes(&mut sender, ": alive\n\n") {
+                break;
+            }
+            last_sent = Instant::now();
+        }
+    }
+}
+
+fn read_from(path: &Path, offset: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    file.take(CHUNK_SIZE).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(unix)]
+fn get_identity(metadata: &Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn get_identity(metadata: &Metadata) -> (u64, u64) {
+    let created = metadata
+        .created()
+        .ok()
+        .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or(0);
+    (0, created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_last_lines_keeps_only_the_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        std::fs::write(&path, "one\r\ntwo\nthree\nfour\n").unwrap();
+        let (lines, offset) = read_last_lines(&path, 2).unwrap();
+        assert_eq!(lines, vec!["three", "four"]);
+        assert_eq!(offset, 20);
+        let (lines, _) = read_last_lines(&path, 10).unwrap();
+        assert_eq!(lines, vec!["one", "two", "three", "four"]);
+    }
+
+    #[test]
+    fn read_last_lines_crosses_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.txt");
+        let line = "x".repeat(CHUNK_SIZE as usize);
+        std::fs::write(&path, format!("{}\nlast\n", line)).unwrap();
+        let (lines, _) = read_last_lines(&path, 2).unwrap();
+        assert_eq!(lines, vec![line.as_str(), "last"]);
+    }
+
+    #[test]
+    fn read_from_reads_one_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.txt");
+        std::fs::write(&path, vec![b'x'; CHUNK_SIZE as usize * 3]).unwrap();
+        assert_eq!(read_from(&path, 0).unwrap().len(), CHUNK_SIZE as usize);
+        assert_eq!(read_from(&path, CHUNK_SIZE * 3 - 5).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn read_last_lines_handles_empty_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("empty.txt");
+        std::fs::write(&path, "").unwrap();
+        let (lines, offset) = read_last_lines(&path, 5).unwrap();
+        assert!(lines.is_empty());
+        assert_eq!(offset, 0);
+    }
+}
@@ -0,0 +1,240 @@
+use actix_web::error::{Error, ErrorBadRequest, ErrorInternalServerError};
+use actix_web::HttpResponse;
+use liz::{liz_dbg_errs, liz_paths};
+use serde::{Deserialize, Serialize};
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::User;
+use crate::body::Body;
+use crate::dirs::{self, Report};
+use crate::guard;
+use crate::quotas;
+use crate::utils::{generate_id, seconds_now};
+use crate::SrvResult;
+
+static TRASH_DIR: &str = ".trash";
+static PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Trashed {
+    pub id: String,
+    pub origin: String,
+    pub is_dir: bool,
+    pub deleted: u64,
+}
+
+pub fn get_trash_dir(for_user: &User) -> Result<String, Error> {
+    liz_paths::path_join(&for_user.home, TRASH_DIR)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, &for_user.home, TRASH_DIR)))
+}
+
+pub fn is_in_trash(path: &str, for_user: &User) -> bool {
+    match get_trash_dir(for_user) {
+        Ok(trash_dir) => path.starts_with(&trash_dir),
+        Err(_) => false,
+    }
+}
+
+pub fn put(path: &str, is_dir: bool, for_user: &User) -> SrvResult {
+    let trash_dir = get_trash_dir(for_user)?;
+    std::fs::create_dir_all(&trash_dir)?;
+    let trashed = Trashed {
+        id: generate_trash_id(),
+        origin: String::from(path),
+        is_dir,
+        deleted: seconds_now(),
+    };
+    let trash_path = Path::new(&trash_dir);
+    move_path(Path::new(path), &trash_path.join(&trashed.id))?;
+    let meta_file = File::create(trash_path.join(format!("{}.json", trashed.id)))?;
+    serde_json::to_writer(meta_file, &trashed)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, trashed)))?;
+    if is_dir {
+        Ok(HttpResponse::Ok().body(format!("Folder trashed: {}", path)))
+    } else {
+        Ok(HttpResponse::Ok().body(format!("File trashed: {}", path)))
+    }
+}
+
+pub fn list(for_user: &User) -> SrvResult {
+    let trashed = read_all(for_user)?;
+    Ok(HttpResponse::Ok().json(trashed))
+}
+
+pub fn restore(id: &str, for_user: &User) -> SrvResult {
+    let trashed = read_one(id, for_user)?;
+    guard::check_dir_access(&trashed.origin, None, "/trash/restore", for_user)?;
+    let origin = Path::new(&trashed.origin);
+    if origin.exists() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The origin to restore already exists",
+            trashed.origin
+        )));
+    }
+    if let Some(parent) = origin.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let trash_path = Path::new(&get_trash_dir(for_user)?).to_owned();
+    move_path(&trash_path.join(&trashed.id), origin)?;
+    std::fs::remove_file(trash_path.join(format!("{}.json", trashed.id)))?;
+    Ok(HttpResponse::Ok().body(format!("Restored on: {}", trashed.origin)))
+}
+
+pub fn purge(id: &Option<String>, for_user: &User) -> SrvResult {
+    if let Some(id) = id {
+        let trashed = read_one(id, for_user)?;
+        remove(&trashed, for_user)?;
+        return Ok(HttpResponse::Ok().body(format!("Purged: {}", trashed.origin)));
+    }
+    let mut purged = 0;
+    for trashed in read_all(for_user)? {
+        remove(&trashed, for_user)?;
+        purged += 1;
+    }
+    Ok(HttpResponse::Ok().body(format!("Purged: {}", purged)))
+}
+
+pub fn purge_expired(retention_days: u64, for_user: &User) -> Result<(), Error> {
+    let retention_secs = retention_days * 24 * 60 * 60;
+    let now = seconds_now();
+    for trashed in read_all(for_user)? {
+        if now.saturating_sub(trashed.deleted) > retention_secs {
+            remove(&trashed, for_user)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn start_purger(body: Arc<Body>) {
+    std::thread::spawn(move || loop {
+        for user in &body.users {
+            if let Err(err) = purge_expired(body.head.trash_retention, user) {
+                eprintln!("{}", liz_dbg_errs!(err, user.name));
+            }
+        }
+        std::thread::sleep(PURGE_INTERVAL);
+    });
+}
+
+fn read_all(for_user: &User) -> Result<Vec<Trashed>, Error> {
+    let mut result = Vec::new();
+    let trash_dir = get_trash_dir(for_user)?;
+    let trash_path = Path::new(&trash_dir);
+    if !trash_path.exists() {
+        return Ok(result);
+    }
+    for entry in trash_path.read_dir()? {
+        let inside = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if !inside.extension().map(|ext| ext == "json").unwrap_or(false) {
+            continue;
+        }
+        let id = match inside.file_stem().and_then(|stem| stem.to_str()) {
+            Some(id) if is_valid_id(id) => id,
+            _ => continue,
+        };
+        match read_meta(&inside, id) {
+            Ok(trashed) => result.push(trashed),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+    result.sort_by(|a, b| b.deleted.cmp(&a.deleted));
+    Ok(result)
+}
+
+fn read_one(id: &str, for_user: &User) -> Result<Trashed, Error> {
+    if !is_valid_id(id) {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The trash identifier is not valid",
+            id
+        )));
+    }
+    let trash_dir = get_trash_dir(for_user)?;
+    let meta_path = Path::new(&trash_dir).join(format!("{}.json", id));
+    if !meta_path.exists() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The trash identifier does not exists",
+            id
+        )));
+    }
+    read_meta(&meta_path, id)
+}
+
+fn read_meta(meta_path: &Path, id: &str) -> Result<Trashed, Error> {
+    let meta_file = File::open(meta_path)?;
+    let mut trashed: Trashed = serde_json::from_reader(meta_file)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, meta_path)))?;
+    trashed.id = String::from(id);
+    Ok(trashed)
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|part| part.is_ascii_alphanumeric() || part == '-')
+}
+
+fn remove(trashed: &Trashed, for_user: &User) -> Result<(), Error> {
+    let trash_path = Path::new(&get_trash_dir(for_user)?).to_owned();
+    let inside = trash_path.join(&trashed.id);
+    let meta_path = trash_path.join(format!("{}.json", trashed.id));
+    let tracked = quotas::track(
+        &[&inside.to_string_lossy(), &meta_path.to_string_lossy()],
+        for_user,
+    );
+    let removed = remove_paths(&inside, &meta_path);
+    tracked.settle();
+    removed
+}
+
+fn remove_paths(inside: &Path, meta_path: &Path) -> Result<(), Error> {
+    if inside.is_dir() {
+        std::fs::remove_dir_all(inside)?;
+    } else if inside.exists() {
+        std::fs::remove_file(inside)?;
+    }
+    std::fs::remove_file(meta_path)?;
+    Ok(())
+}
+
+fn move_path(origin: &Path, destiny: &Path) -> Result<(), Error> {
+    let mut report = Report::new(&origin.to_string_lossy(), &destiny.to_string_lossy());
+    dirs::move_all(origin, destiny, &mut report)?;
+    if !report.failed.is_empty() {
+        return Err(ErrorInternalServerError(liz_dbg_errs!(
+            "Could not move all the entries",
+            report
+        )));
+    }
+    Ok(())
+}
+
+fn generate_trash_id() -> String {
+    format!("{}-{}", seconds_now(), generate_id(8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_valid_id_accepts_generated_ids() {
+        assert!(is_valid_id(&generate_trash_id()));
+    }
+
+    #[test]
+    fn is_valid_id_rejects_paths() {
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("../../other/dir"));
+        assert!(!is_valid_id("a/b"));
+        assert!(!is_valid_id("a\\b"));
+        assert!(!is_valid_id("a.b"));
+    }
+}
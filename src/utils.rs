@@ -0,0 +1,104 @@
+use actix_web::web::Bytes;
+use futures::channel::mpsc::{self, Receiver, Sender};
+use futures::executor::block_on;
+use futures::SinkExt;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static EVENT_BUFFER: usize = 64;
+
+pub type EventSender = Sender<Result<Bytes, std::io::Error>>;
+pub type EventReceiver = Receiver<Result<Bytes, std::io::Error>>;
+
+pub fn seconds_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn generate_id(size: usize) -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(size)
+        .map(char::from)
+        .collect()
+}
+
+pub fn event_channel() -> (EventSender, EventReceiver) {
+    mpsc::channel(EVENT_BUFFER)
+}
+
+/// Blocks until the client takes the bytes, so call it only from our own threads.
+pub fn send_bytes(sender: &mut EventSender, bytes: impl Into<Bytes>) -> bool {
+    block_on(sender.send(Ok(bytes.into()))).is_ok()
+}
+
+pub fn send_event(sender: &mut EventSender, kind: &str, data: &str) -> bool {
+    send_bytes(sender, format_event(kind, data))
+}
+
+fn format_event(kind: &str, data: &str) -> String {
+    let mut event = format!("event: {}\n", kind);
+    for line in data.split('\n') {
+        event.push_str("data: ");
+        event.push_str(line);
+        event.push('\n');
+    }
+    event.push('\n');
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_event_single_line() {
+        assert_eq!(
+            format_event("line", "hello"),
+            "event: line\ndata: hello\n\n"
+        );
+    }
+
+    #[test]
+    fn format_event_multi_line() {
+        assert_eq!(
+            format_event("stdout", "one\ntwo"),
+            "event: stdout\ndata: one\ndata: two\n\n"
+        );
+    }
+
+    #[test]
+    fn send_bytes_waits_for_room() {
+        let (mut sender, mut receiver) = event_channel();
+        let reader = std::thread::spawn(move || {
+            let mut count = 0;
+            while let Some(Ok(_)) = block_on(futures::StreamExt::next(&mut receiver)) {
+                count += 1;
+            }
+            count
+        });
+        for _ in 0..EVENT_BUFFER * 4 {
+            assert!(send_bytes(&mut sender, "data"));
+        }
+        drop(sender);
+        assert_eq!(reader.join().unwrap(), EVENT_BUFFER * 4);
+    }
+
+    #[test]
+    fn send_bytes_fails_when_closed() {
+        let (mut sender, receiver) = event_channel();
+        drop(receiver);
+        assert!(!send_bytes(&mut sender, "data"));
+    }
+
+    #[test]
+    fn generate_id_has_size() {
+        let id = generate_id(24);
+        assert_eq!(id.len(), 24);
+        assert!(id.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+}
@@ -0,0 +1,185 @@
+use actix_files::NamedFile;
+use actix_web::error::{Error, ErrorBadRequest};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use crate::auth::{Access, User};
+use crate::conf::Head;
+use crate::hashes::{self, Algorithm};
+use crate::quotas;
+use crate::SrvResult;
+
+static VERSIONS_OBJ_DIR: &str = "ver/obj";
+static VERSIONS_IDX_DIR: &str = "ver/idx";
+
+static VERSIONS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Versions {
+    pub path: String,
+    pub versions: Vec<Version>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Version {
+    pub id: u64,
+    pub hash: String,
+    pub size: u64,
+    pub time: u64,
+}
+
+pub fn get_limit(path: &str, for_user: &User, head: &Head) -> u64 {
+    let mut found_len = 0;
+    let mut found_limit = None;
+    for user_access in &for_user.access {
+        if let Access::DIR {
+            path: grant,
+            versions: Some(versions),
+            ..
+        } = user_access
+        {
+            if path.starts_with(grant) && grant.len() >= found_len {
+                found_len = grant.len();
+                found_limit = Some(*versions);
+            }
+        }
+    }
+    found_limit.unwrap_or(head.file_versions)
+}
+
+pub fn save(path: &str, limit: u64) -> Result<(), Error> {
+    if limit == 0 || !Path::new(path).is_file() {
+        return Ok(());
+    }
+    let _locked = VERSIONS_LOCK.lock().unwrap();
+    let mut index = read_index(path)?;
+    let hash = hashes::hash_file(Path::new(path), Algorithm::Sha256)?;
+    if let Some(last) = index.versions.last() {
+        if last.hash == hash {
+            return Ok(());
+        }
+    }
+    std::fs::create_dir_all(VERSIONS_OBJ_DIR)?;
+    let object = get_object_path(&hash);
+    if !object.exists() {
+        std::fs::copy(path, &object)?;
+    }
+    let metadata = std::fs::metadata(path)?;
+    let time = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    let id = index.versions.last().map(|last| last.id + 1).unwrap_or(1);
+    index.versions.push(Version {
+        id,
+        hash,
+        size: metadata.len(),
+        time,
+    });
+    let mut dropped = Vec::new();
+    while index.versions.len() as u64 > limit {
+        dropped.push(index.versions.remove(0));
+    }
+    write_index(&index)?;
+    for version in dropped {
+        if !is_referenced(&version.hash)? {
+            std::fs::remove_file(get_object_path(&version.hash))?;
+        }
+    }
+    Ok(())
+}
+
+pub fn list(path: &str) -> SrvResult {
+    let _locked = VERSIONS_LOCK.lock().unwrap();
+    let index = read_index(path)?;
+    Ok(HttpResponse::Ok().json(index))
+}
+
+pub fn read(path: &str, id: u64) -> Result<NamedFile, Error> {
+    let _locked = VERSIONS_LOCK.lock().unwrap();
+    let version = find_version(path, id)?;
+    Ok(NamedFile::open(get_object_path(&version.hash))?)
+}
+
+pub fn restore(path: &str, id: u64, limit: u64, for_user: &User) -> SrvResult {
+    let restored = {
+        let _locked = VERSIONS_LOCK.lock().unwrap();
+        let version = find_version(path, id)?;
+        std::fs::read(get_object_path(&version.hash))?
+    };
+    let existing = std::fs::metadata(path).ok().map(|metadata| metadata.len());
+    let grows = (restored.len() as u64).saturating_sub(existing.unwrap_or(0));
+    let files = if existing.is_none() { 1 } else { 0 };
+    let reserved = quotas::reserve(path, for_user, grows, files)?;
+    save(path, limit.max(1))?;
+    std::fs::write(path, &restored)?;
+    reserved.keep();
+    Ok(HttpResponse::Ok().body(format!("Restored version: {} on: {}", id, path)))
+}
+
+fn find_version(path: &str, id: u64) -> Result<Version, Error> {
+    let index = read_index(path)?;
+    index
+        .versions
+        .into_iter()
+        .find(|version| version.id == id)
+        .ok_or_else(|| ErrorBadRequest(liz_dbg_errs!("The version does not exists", path, id)))
+}
+
+fn read_index(path: &str) -> Result<Versions, Error> {
+    let index_path = get_index_path(path);
+    if !index_path.exists() {
+        return Ok(Versions {
+            path: String::from(path),
+            versions: Vec::new(),
+        });
+    }
+    let index_file = File::open(&index_path)?;
+    serde_json::from_reader(index_file).map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, path)))
+}
+
+fn write_index(index: &Versions) -> Result<(), Error> {
+    std::fs::create_dir_all(VERSIONS_IDX_DIR)?;
+    let index_path = get_index_path(&index.path);
+    if index.versions.is_empty() {
+        if index_path.exists() {
+            std::fs::remove_file(index_path)?;
+        }
+        return Ok(());
+    }
+    let index_file = File::create(&index_path)?;
+    serde_json::to_writer(index_file, index)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, index.path)))
+}
+
+fn is_referenced(hash: &str) -> Result<bool, Error> {
+    for entry in Path::new(VERSIONS_IDX_DIR).read_dir()? {
+        let entry = entry?;
+        let index_file = File::open(entry.path())?;
+        let index: Versions = serde_json::from_reader(index_file)
+            .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, entry.path())))?;
+        if index.versions.iter().any(|version| version.hash == hash) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn get_index_path(path: &str) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    Path::new(VERSIONS_IDX_DIR).join(format!("{:x}.json", hasher.finalize()))
+}
+
+fn get_object_path(hash: &str) -> PathBuf {
+    Path::new(VERSIONS_OBJ_DIR).join(hash)
+}
@@ -0,0 +1,98 @@
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::HttpResponse;
+use liz::liz_dbg_errs;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use crate::utils::{event_channel, send_bytes, EventSender};
+use crate::SrvResult;
+
+static DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
+static KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub kind: &'static str,
+    pub path: String,
+    pub destiny: Option<String>,
+}
+
+pub fn watch(path: &str, recursive: bool) -> SrvResult {
+    let pathed = Path::new(path);
+    if !pathed.is_dir() {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path to watch is not a directory",
+            path
+        )));
+    }
+    let (notify_sender, notify_receiver) = std::sync::mpsc::channel();
+    let mut watcher = notify::watcher(notify_sender, DEBOUNCE_DELAY)
+        .map_err(|err| ErrorInternalServerError(liz_dbg_errs!(err, path)))?;
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(pathed, mode)
+        .map_err(|err| ErrorInternalServerError(liz_dbg_errs!(err, path)))?;
+    let (mut sender, receiver) = event_channel();
+    std::thread::spawn(move || {
+        let _watcher = watcher;
+        loop {
+            let sent = match notify_receiver.recv_timeout(KEEP_ALIVE) {
+                Ok(event) => match get_change(event) {
+                    Some(change) => send_change(&mut sender, &change),
+                    None => true,
+                },
+                Err(RecvTimeoutError::Timeout) => send_bytes(&mut sender, ": alive\n\n"),
+                Err(RecvTimeoutError::Disconnected) => false,
+            };
+            if !sent {
+                break;
+            }
+        }
+    });
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(receiver))
+}
+
+fn get_change(event: DebouncedEvent) -> Option<Change> {
+    match event {
+        DebouncedEvent::Create(path) => Some(new_change("create", path, None)),
+        DebouncedEvent::Write(path) => Some(new_change("modify", path, None)),
+        DebouncedEvent::Chmod(path) => Some(new_change("modify", path, None)),
+        DebouncedEvent::Remove(path) => Some(new_change("delete", path, None)),
+        DebouncedEvent::Rename(origin, destiny) => {
+            Some(new_change("rename", origin, Some(destiny)))
+        }
+        DebouncedEvent::Rescan => Some(Change {
+            kind: "rescan",
+            path: String::new(),
+            destiny: None,
+        }),
+        _ => None,
+    }
+}
+
+fn new_change(kind: &'static str, path: PathBuf, destiny: Option<PathBuf>) -> Change {
+    Change {
+        kind,
+        path: format!("{}", path.display()),
+        destiny: destiny.map(|destiny| format!("{}", destiny.display())),
+    }
+}
+
+fn send_change(sender: &mut EventSender, change: &Change) -> bool {
+    let data = serde_json::to_string(change).unwrap_or_default();
+    send_bytes(
+        sender,
+        format!("event: {}\ndata: {}\n\n", change.kind, data),
+    )
+}
@@ -0,0 +1,622 @@
+use actix_files::NamedFile;
+use actix_web::error::{
+    Error, ErrorBadRequest, ErrorLengthRequired, ErrorNotFound, ErrorPayloadTooLarge, InternalError,
+};
+use actix_web::http::header::HttpDate;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures::StreamExt;
+use liz::{liz_dbg_errs, liz_paths};
+use once_cell::sync::Lazy;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use std::fs::{File, Metadata};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth::User;
+use crate::conflicts::Conflict;
+use crate::dirs;
+use crate::files;
+use crate::guard;
+use crate::quotas;
+use crate::utils::generate_id;
+use crate::versions;
+use crate::SrvData;
+use crate::SrvResult;
+
+static LOCK_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+static LOCKS: Lazy<Mutex<Vec<DavLock>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+#[derive(Debug, Clone)]
+struct DavLock {
+    token: String,
+    path: String,
+    owner: String,
+    deep: bool,
+    expires: Instant,
+}
+
+pub async fn serve(req: HttpRequest, payload: web::Payload, srv_data: SrvData) -> SrvResult {
+    let user =
+        match guard::get_user(&req, &srv_data).or_else(|| guard::get_basic_user(&req, &srv_data)) {
+            Some(user) => user,
+            None => {
+                return Ok(HttpResponse::Unauthorized()
+                    .header(
+                        "WWW-Authenticate",
+                        format!("Basic realm=\"{}\"", srv_data.head.server_name),
+                    )
+                    .finish())
+            }
+        };
+    let prefix = srv_data.head.webdav_prefix.clone().unwrap_or_default();
+    let tail = req.match_info().query("tail");
+    let path = get_local_path(tail, user)?;
+    match req.method().as_str() {
+        "OPTIONS" => Ok(HttpResponse::Ok()
+            .header("DAV", "1, 2")
+            .header("MS-Author-Via", "DAV")
+            .header(
+                "Allow",
+                "OPTIONS, PROPFIND, PROPPATCH, GET, HEAD, PUT, MKCOL, COPY, MOVE, DELETE, LOCK, UNLOCK",
+            )
+            .finish()),
+        "PROPFIND" => propfind(&req, &path, &prefix, user),
+        "PROPPATCH" => proppatch(&req, &path, &prefix, user),
+        "GET" | "HEAD" => get(&req, &path, user),
+        "PUT" => put(&req, payload, &path, user, &srv_data).await,
+        "MKCOL" => mkcol(&path, user),
+        "DELETE" => delete(&req, &path, user, &srv_data),
+        "COPY" => transfer(&req, &path, &prefix, false, user),
+        "MOVE" => transfer(&req, &path, &prefix, true, user),
+        "LOCK" => lock(&req, &path, &prefix, user),
+        "UNLOCK" => unlock(&req, &path, user),
+        _ => Ok(HttpResponse::MethodNotAllowed().finish()),
+    }
+}
+
+fn propfind(req: &HttpRequest, path: &str, prefix: &str, user: &User) -> SrvResult {
+    let pathed = Path::new(path);
+    if !pathed.exists() {
+        return Err(ErrorNotFound(liz_dbg_errs!(
+            "Could not found the resource",
+            path
+        )));
+    }
+    let resource = if pathed.is_dir() {
+        "/dir/list"
+    } else {
+        "/file/read"
+    };
+    guard::check_dir_access(path, None, resource, user)?;
+    let depth = req
+        .headers()
+        .get("Depth")
+        .and_then(|depth| depth.to_str().ok())
+        .unwrap_or("infinity");
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    push_response(&mut body, pathed, &std::fs::metadata(pathed)?, prefix, user);
+    if pathed.is_dir() && depth != "0" {
+        for entry in pathed.read_dir()? {
+            let inside = match entry {
+                Ok(entry) => entry.path(),
+                Err(_) => continue,
+            };
+            if let Some(metadata) = get_listed_metadata(&inside, user) {
+                push_response(&mut body, &inside, &metadata, prefix, user);
+            }
+        }
+    }
+    body.push_str("</D:multistatus>\n");
+    Ok(HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(body))
+}
+
+fn proppatch(req: &HttpRequest, path: &str, prefix: &str, user: &User) -> SrvResult {
+    guard::check_dir_access(path, None, "/file/write", user)?;
+    check_locks(req, path)?;
+    let mut body = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    body.push_str(&format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop/><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n",
+        get_href(Path::new(path), prefix, user)
+    ));
+    body.push_str("</D:multistatus>\n");
+    Ok(HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(body))
+}
+
+fn get(req: &HttpRequest, path: &str, user: &User) -> SrvResult {
+    if Path::new(path).is_dir() {
+        guard::check_dir_access(path, None, "/dir/list", user)?;
+        return dirs::list(path, false, false);
+    }
+    guard::check_dir_access(path, None, "/file/read", user)?;
+    Ok(NamedFile::open(path)?.into_response(req)?)
+}
+
+async fn put(
+    req: &HttpRequest,
+    mut payload: web::Payload,
+    path: &str,
+    user: &User,
+    srv_data: &SrvData,
+) -> SrvResult {
+    guard::check_dir_access(path, None, "/file/write", user)?;
+    check_locks(req, path)?;
+    let pathed = Path::new(path);
+    if pathed.is_dir() {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
+    }
+    if !pathed
+        .parent()
+        .map(|parent| parent.is_dir())
+        .unwrap_or(false)
+    {
+        return Ok(HttpResponse::Conflict().finish());
+    }
+    let cap = srv_data.head.webdav_put_cap;
+    let declared = req
+        .headers()
+        .get("Content-Length")
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<u64>().ok());
+    if declared.map_or(false, |declared| declared > cap) {
+        return Err(too_large(path, cap));
+    }
+    let limited = quotas::get_quota(user).is_some() && quotas::is_counted(path, user);
+    if limited && declared.is_none() {
+        return Err(ErrorLengthRequired(liz_dbg_errs!(
+            "The put on a path with quota must declare its length",
+            path
+        )));
+    }
+    let staging = quotas::reserve(path, user, declared.unwrap_or(0), 1)?;
+    let cap = declared.map_or(cap, |declared| declared.min(cap));
+    let staged = get_staged_path(pathed);
+    let received = receive(&mut payload, &staged, cap).await;
+    drop(staging);
+    let existed = pathed.exists();
+    let keep_versions = versions::get_limit(path, user, &srv_data.head);
+    let written = received.and_then(|_| files::write_staged(path, &staged, keep_versions, user));
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&staged);
+        return Err(err);
+    }
+    if existed {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::Created().finish())
+    }
+}
+
+async fn receive(payload: &mut web::Payload, staged: &Path, cap: u64) -> Result<(), Error> {
+    let mut file = File::create(staged)?;
+    let mut size: u64 = 0;
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        size += chunk.len() as u64;
+        if size > cap {
+            return Err(too_large(&format!("{}", staged.display()), cap));
+        }
+        file.write_all(&chunk)?;
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+fn get_staged_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.put", name, generate_id(8)))
+}
+
+fn too_large(path: &str, cap: u64) -> Error {
+    ErrorPayloadTooLarge(liz_dbg_errs!(
+        "The body is larger than the put cap",
+        path,
+        cap
+    ))
+}
+
+fn mkcol(path: &str, user: &User) -> SrvResult {
+    guard::check_dir_access(path, None, "/dir/new", user)?;
+    let pathed = Path::new(path);
+    if pathed.exists() {
+        return Ok(HttpResponse::MethodNotAllowed().finish());
+    }
+    if !pathed
+        .parent()
+        .map(|parent| parent.is_dir())
+        .unwrap_or(false)
+    {
+        return Ok(HttpResponse::Conflict().finish());
+    }
+    std::fs::create_dir(pathed)?;
+    Ok(HttpResponse::Created().finish())
+}
+
+fn delete(req: &HttpRequest, path: &str, user: &User, srv_data: &SrvData) -> SrvResult {
+    let is_dir = Path::new(path).is_dir();
+    let resource = if is_dir { "/dir/del" } else { "/file/del" };
+    guard::check_dir_access(path, None, resource, user)?;
+    check_locks(req, path)?;
+    if is_dir {
+        dirs::del(path, srv_data.head.dirs_trash, user)?;
+    } else {
+        files::del(path, srv_data.head.dirs_trash, user)?;
+    }
+    forget_locks(path);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn transfer(req: &HttpRequest, path: &str, prefix: &str, moving: bool, user: &User) -> SrvResult {
+    let destination = req
+        .headers()
+        .get("Destination")
+        .and_then(|destination| destination.to_str().ok())
+        .ok_or_else(|| ErrorBadRequest("The destination header was not informed"))?;
+    let destiny = get_destiny_path(destination, prefix, user)?;
+    let overwrite = req
+        .headers()
+        .get("Overwrite")
+        .and_then(|overwrite| overwrite.to_str().ok())
+        .map(|overwrite| overwrite != "F")
+        .unwrap_or(true);
+    let is_dir = Path::new(path).is_dir();
+    let resource = match (is_dir, moving) {
+        (true, true) => "/dir/move",
+        (true, false) => "/dir/copy",
+        (false, true) => "/file/move",
+        (false, false) => "/file/copy",
+    };
+    guard::check_dir_access(path, Some(&destiny), resource, user)?;
+    if moving {
+        check_locks(req, path)?;
+    }
+    check_locks(req, &destiny)?;
+    let existed = Path::new(&destiny).exists();
+    if existed && !overwrite {
+        return Ok(HttpResponse::PreconditionFailed().finish());
+    }
+    let conflict = Some(Conflict::Overwrite);
+    match (is_dir, moving) {
+        (true, true) => dirs::mov(path, &destiny, conflict, false, user)?,
+        (true, false) => dirs::copy(path, &destiny, conflict, false, user)?,
+        (false, true) => files::mov(path, &destiny, conflict, false, user)?,
+        (false, false) => files::copy(path, &destiny, conflict, false, user)?,
+    };
+    if moving {
+        forget_locks(path);
+    }
+    if existed {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::Created().finish())
+    }
+}
+
+fn lock(req: &HttpRequest, path: &str, prefix: &str, user: &User) -> SrvResult {
+    guard::check_dir_access(path, None, "/file/write", user)?;
+    let now = Instant::now();
+    let mut locks = LOCKS.lock().unwrap();
+    locks.retain(|lock| lock.expires > now);
+    let if_header = get_if_header(req);
+    if let Some(refreshed) = locks
+        .iter_mut()
+        .find(|lock| lock.path == path && if_header.contains(&lock.token))
+    {
+        refreshed.expires = now + LOCK_TIMEOUT;
+        let refreshed = refreshed.clone();
+        return Ok(lock_response(&refreshed, prefix, user, StatusCode::OK));
+    }
+    if locks.iter().any(|lock| is_locking(lock, path)) {
+        return Err(locked(path));
+    }
+    let deep = req
+        .headers()
+        .get("Depth")
+        .and_then(|depth| depth.to_str().ok())
+        .map(|depth| depth != "0")
+        .unwrap_or(true);
+    let pathed = Path::new(path);
+    let mut status = StatusCode::OK;
+    if !pathed.exists() {
+        let reserved = quotas::reserve(path, user, 0, 1)?;
+        File::create(pathed)?;
+        reserved.keep();
+        status = StatusCode::CREATED;
+    }
+    let token: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    let created = DavLock {
+        token: format!("opaquelocktoken:{}", token),
+        path: String::from(path),
+        owner: user.name.clone(),
+        deep,
+        expires: now + LOCK_TIMEOUT,
+    };
+    locks.push(created.clone());
+    Ok(lock_response(&created, prefix, user, status))
+}
+
+fn unlock(req: &HttpRequest, path: &str, user: &User) -> SrvResult {
+    let token = req
+        .headers()
+        .get("Lock-Token")
+        .and_then(|token| token.to_str().ok())
+        .map(|token| {
+            token
+                .trim_matches(|c| c == '<' || c == '>' || c == ' ')
+                .to_string()
+        })
+        .ok_or_else(|| ErrorBadRequest("The lock token header was not informed"))?;
+    let mut locks = LOCKS.lock().unwrap();
+    let position = locks
+        .iter()
+        .position(|lock| lock.token == token && lock.path == path)
+        .ok_or_else(|| InternalError::new("The lock token does not match", StatusCode::CONFLICT))?;
+    if locks[position].owner != user.name && !user.master {
+        return Err(locked(path));
+    }
+    locks.remove(position);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn lock_response(lock: &DavLock, prefix: &str, user: &User, status: StatusCode) -> HttpResponse {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>\n",
+        get_active_lock(lock, prefix, user)
+    );
+    HttpResponse::build(status)
+        .header("Lock-Token", format!("<{}>", lock.token))
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+fn get_active_lock(lock: &DavLock, prefix: &str, user: &User) -> String {
+    let remaining = lock
+        .expires
+        .saturating_duration_since(Instant::now())
+        .as_secs();
+    format!(
+        "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>{}</D:depth><D:owner>{}</D:owner><D:timeout>Second-{}</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+        if lock.deep { "infinity" } else { "0" },
+        escape_xml(&lock.owner),
+        remaining,
+        lock.token,
+        get_href(Path::new(&lock.path), prefix, user)
+    )
+}
+
+fn check_locks(req: &HttpRequest, path: &str) -> Result<(), Error> {
+    let now = Instant::now();
+    let if_header = get_if_header(req);
+    let locks = LOCKS.lock().unwrap();
+    for lock in locks.iter() {
+        if lock.expires > now && is_locking(lock, path) && !if_header.contains(&lock.token) {
+            return Err(locked(path));
+        }
+    }
+    Ok(())
+}
+
+fn forget_locks(path: &str) {
+    LOCKS
+        .lock()
+        .unwrap()
+        .retain(|lock| !Path::new(&lock.path).starts_with(path));
+}
+
+fn is_locking(lock: &DavLock, path: &str) -> bool {
+    let locked_path = Path::new(&lock.path);
+    let target = Path::new(path);
+    target == locked_path
+        || (lock.deep && target.starts_with(locked_path))
+        || locked_path.starts_with(target)
+}
+
+fn get_if_header(req: &HttpRequest) -> String {
+    req.headers()
+        .get("If")
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+fn locked(path: &str) -> Error {
+    InternalError::new(
+        liz_dbg_errs!("The resource is locked", path),
+        StatusCode::LOCKED,
+    )
+    .into()
+}
+
+fn get_listed_metadata(path: &Path, user: &User) -> Option<Metadata> {
+    let metadata = std::fs::symlink_metadata(path).ok()?;
+    let metadata = if metadata.file_type().is_symlink() {
+        std::fs::metadata(path).ok()?
+    } else {
+        metadata
+    };
+    let resource = if metadata.is_dir() {
+        "/dir/list"
+    } else {
+        "/file/read"
+    };
+    let path_str = format!("{}", path.display());
+    guard::check_dir_access(&path_str, None, resource, user).ok()?;
+    Some(metadata)
+}
+
+fn push_response(body: &mut String, path: &Path, metadata: &Metadata, prefix: &str, user: &User) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    body.push_str("<D:response><D:href>");
+    body.push_str(&get_href(path, prefix, user));
+    body.push_str("</D:href><D:propstat><D:prop>");
+    body.push_str(&format!(
+        "<D:displayname>{}</D:displayname>",
+        escape_xml(&name)
+    ));
+    if metadata.is_dir() {
+        body.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+    } else {
+        body.push_str("<D:resourcetype/>");
+        body.push_str(&format!(
+            "<D:getcontentlength>{}</D:getcontentlength>",
+            metadata.len()
+        ));
+    }
+    if let Ok(modified) = metadata.modified() {
+        body.push_str(&format!(
+            "<D:getlastmodified>{}</D:getlastmodified>",
+            HttpDate::from(modified)
+        ));
+    }
+    body.push_str("<D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>");
+    body.push_str("<D:lockdiscovery>");
+    let path_str = format!("{}", path.display());
+    let now = Instant::now();
+    for lock in LOCKS.lock().unwrap().iter() {
+        if lock.expires > now && lock.path == path_str {
+            body.push_str(&get_active_lock(lock, prefix, user));
+        }
+    }
+    body.push_str("</D:lockdiscovery>");
+    body.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+}
+
+fn get_local_path(tail: &str, user: &User) -> Result<String, Error> {
+    let tail = tail.trim_matches('/');
+    if tail.is_empty() {
+        return Ok(user.home.clone());
+    }
+    if Path::new(tail)
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(ErrorBadRequest(liz_dbg_errs!(
+            "The path is not valid",
+            tail
+        )));
+    }
+    liz_paths::path_join(&user.home, tail)
+        .map_err(|err| ErrorBadRequest(liz_dbg_errs!(err, &user.home, tail)))
+}
+
+fn get_destiny_path(destination: &str, prefix: &str, user: &User) -> Result<String, Error> {
+    let mut destiny = destination;
+    if let Some(scheme_end) = destiny.find("://") {
+        let after_scheme = &destiny[scheme_end + 3..];
+        destiny = after_scheme
+            .find('/')
+            .map(|slash| &after_scheme[slash..])
+            .unwrap_or("/");
+    }
+    let destiny = destiny.strip_prefix(prefix).ok_or_else(|| {
+        ErrorBadRequest(liz_dbg_errs!(
+            "The destination is outside of this service",
+            destination
+        ))
+    })?;
+    get_local_path(&decode_percent(destiny), user)
+}
+
+fn get_href(path: &Path, prefix: &str, user: &User) -> String {
+    let mut href = String::from(prefix);
+    if let Ok(relative) = path.strip_prefix(&user.home) {
+        for component in relative.components() {
+            href.push('/');
+            href.push_str(&encode_percent(&component.as_os_str().to_string_lossy()));
+        }
+    }
+    if path.is_dir() {
+        href.push('/');
+    }
+    href
+}
+
+fn encode_percent(segment: &str) -> String {
+    let mut result = String::new();
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    result
+}
+
+fn decode_percent(source: &str) -> String {
+    let bytes = source.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 3 <= bytes.len() {
+            let escaped = &bytes[index + 1..index + 3];
+            if escaped.iter().all(|byte| byte.is_ascii_hexdigit()) {
+                if let Ok(byte) = u8::from_str_radix(&String::from_utf8_lossy(escaped), 16) {
+                    result.push(byte);
+                    index += 3;
+                    continue;
+                }
+            }
+        }
+        result.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+fn escape_xml(source: &str) -> String {
+    source
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_percent_handles_escapes_at_the_end() {
+        assert_eq!(decode_percent("a%20b"), "a b");
+        assert_eq!(decode_percent("name%21"), "name!");
+        assert_eq!(decode_percent("%41"), "A");
+    }
+
+    #[test]
+    fn decode_percent_keeps_invalid_escapes() {
+        assert_eq!(decode_percent("100%"), "100%");
+        assert_eq!(decode_percent("100%4"), "100%4");
+        assert_eq!(decode_percent("a%zzb"), "a%zzb");
+        assert_eq!(decode_percent("a%+1b"), "a%+1b");
+    }
+
+    #[test]
+    fn decode_percent_reverts_encode_percent() {
+        let source = "relatório (1) & más.txt";
+        assert_eq!(decode_percent(&encode_percent(source)), source);
+    }
+}